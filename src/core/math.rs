@@ -1 +1,7 @@
+pub mod aabb;
+pub mod bvh;
+pub mod collider;
 pub mod curve;
+pub mod error;
+pub mod line;
+pub mod poly;
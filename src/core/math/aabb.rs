@@ -0,0 +1,105 @@
+//! axis aligned bounding boxes
+
+use bevy::math::{DVec3, Vec3};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// contains nothing, the identity of [`Self::union`]
+    pub fn empty() -> Self {
+        Self::new(Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY))
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        points
+            .into_iter()
+            .fold(Self::empty(), |aabb, p| aabb.with_point(p))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    pub fn with_point(&self, p: Vec3) -> Self {
+        Self::new(self.min.min(p), self.max.max(p))
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    /// grow every side by |margin|
+    pub fn expand(&self, margin: f32) -> Self {
+        Self::new(
+            self.min - Vec3::splat(margin),
+            self.max + Vec3::splat(margin),
+        )
+    }
+
+    /// the box in f32 world coordinates, for a box kept relative to an f64 |origin|. it is
+    /// grown by what rounding to f32 costs out there, so it still holds everything it did.
+    pub fn placed_at(&self, origin: DVec3) -> Self {
+        let (min, max) = (origin + self.min.as_dvec3(), origin + self.max.as_dvec3());
+        let slack = min.abs().max(max.abs()).max_element() as f32 * f32::EPSILON;
+        Self::new(min.as_vec3(), max.as_vec3()).expand(slack)
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn contains(&self, p: Vec3) -> bool {
+        p.cmpge(self.min).all() && p.cmple(self.max).all()
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.min.cmple(other.max).all() && other.min.cmple(self.max).all()
+    }
+
+    /// like [`Self::intersects`] but looking from above, y is ignored
+    pub fn intersects_xz(&self, other: &Self) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.z <= other.max.z
+            && other.min.z <= self.max.z
+    }
+
+    /// 0 inside the box
+    pub fn distance_to(&self, p: Vec3) -> f32 {
+        (self.min - p).max(p - self.max).max(Vec3::ZERO).length()
+    }
+
+    /// the distance along |direction| where the ray from |origin| enters the box, 0 when it
+    /// starts inside. None if the ray misses. |direction| does not need to be normalized, the
+    /// result is in multiples of it.
+    pub fn ray_hit(&self, origin: Vec3, direction: Vec3) -> Option<f32> {
+        let inv = direction.recip();
+        let t0 = (self.min - origin) * inv;
+        let t1 = (self.max - origin) * inv;
+        // a zero direction gives nan when the origin is on a face, count it as inside
+        let near = t0.min(t1);
+        let far = t0.max(t1);
+        let near = [near.x, near.y, near.z]
+            .into_iter()
+            .filter(|t| !t.is_nan())
+            .fold(0.0_f32, f32::max);
+        let far = [far.x, far.y, far.z]
+            .into_iter()
+            .filter(|t| !t.is_nan())
+            .fold(f32::INFINITY, f32::min);
+        (near <= far).then_some(near)
+    }
+}
@@ -0,0 +1,238 @@
+//! a bounding volume hierarchy over anything with an [`Aabb`].
+//!
+//! the tree is built once, top-down, splitting at the median of the box centers on the
+//! longest axis. rebuild it when the items move; it is cheap enough for a few thousand roads.
+
+use bevy::math::Vec3;
+
+use super::aabb::Aabb;
+
+/// at most this many items in a leaf
+const LEAF_SIZE: usize = 4;
+
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf {
+        aabb: Aabb,
+        /// range in `Bvh::items`
+        start: usize,
+        end: usize,
+    },
+    Branch {
+        aabb: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl Node {
+    fn aabb(&self) -> &Aabb {
+        match self {
+            Node::Leaf { aabb, .. } | Node::Branch { aabb, .. } => aabb,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Bvh<T> {
+    items: Vec<(Aabb, T)>,
+    nodes: Vec<Node>,
+}
+
+impl<T> Default for Bvh<T> {
+    fn default() -> Self {
+        Self {
+            items: vec![],
+            nodes: vec![],
+        }
+    }
+}
+
+impl<T> Bvh<T> {
+    pub fn new(mut items: Vec<(Aabb, T)>) -> Self {
+        let mut nodes = vec![];
+        if !items.is_empty() {
+            let len = items.len();
+            build(&mut items, 0, len, &mut nodes);
+        }
+        Self { items, nodes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Aabb, T)> {
+        self.items.iter()
+    }
+
+    /// items whose box is within |radius| of |point|
+    pub fn query_point(&self, point: Vec3, radius: f32) -> Vec<&T> {
+        self.query(|aabb| aabb.distance_to(point) <= radius)
+    }
+
+    /// items whose box is within |radius| of the segment from |p| to |q|
+    pub fn query_segment(&self, p: Vec3, q: Vec3, radius: f32) -> Vec<&T> {
+        self.query(|aabb| {
+            aabb.expand(radius)
+                .ray_hit(p, q - p)
+                .is_some_and(|t| t <= 1.0)
+        })
+    }
+
+    /// items whose box overlaps |aabb|
+    pub fn query_aabb(&self, aabb: &Aabb) -> Vec<&T> {
+        self.query(|other| other.intersects(aabb))
+    }
+
+    /// items whose box is hit by the ray, with the distance (in multiples of |direction|)
+    /// where the ray enters it, nearest first
+    pub fn query_ray(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Vec<(f32, &T)> {
+        let mut hits = vec![];
+        self.visit(
+            |aabb| {
+                aabb.ray_hit(origin, direction)
+                    .is_some_and(|t| t <= max_distance)
+            },
+            |aabb, item| {
+                if let Some(t) = aabb.ray_hit(origin, direction) {
+                    if t <= max_distance {
+                        hits.push((t, item));
+                    }
+                }
+            },
+        );
+        hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+        hits
+    }
+
+    fn query(&self, accept: impl Fn(&Aabb) -> bool) -> Vec<&T> {
+        let mut found = vec![];
+        self.visit(&accept, |aabb, item| {
+            if accept(aabb) {
+                found.push(item);
+            }
+        });
+        found
+    }
+
+    /// call |on_item| for every item in the nodes that pass |accept|
+    fn visit<'a>(&'a self, accept: impl Fn(&Aabb) -> bool, mut on_item: impl FnMut(&Aabb, &'a T)) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !accept(node.aabb()) {
+                continue;
+            }
+            match node {
+                Node::Leaf { start, end, .. } => self.items[*start..*end]
+                    .iter()
+                    .for_each(|(aabb, item)| on_item(aabb, item)),
+                Node::Branch { left, right, .. } => {
+                    stack.push(*left);
+                    stack.push(*right);
+                }
+            }
+        }
+    }
+}
+
+/// build the subtree over items[start..end] and return its index
+fn build<T>(items: &mut [(Aabb, T)], start: usize, end: usize, nodes: &mut Vec<Node>) -> usize {
+    let aabb = items[start..end]
+        .iter()
+        .fold(Aabb::empty(), |acc, (aabb, _)| acc.union(aabb));
+    let index = nodes.len();
+    if end - start <= LEAF_SIZE {
+        nodes.push(Node::Leaf { aabb, start, end });
+        return index;
+    }
+    // reserve the slot, the children are pushed after it
+    nodes.push(Node::Leaf {
+        aabb,
+        start: 0,
+        end: 0,
+    });
+    let size = aabb.size();
+    let axis = if size.x >= size.y && size.x >= size.z {
+        0
+    } else if size.y >= size.z {
+        1
+    } else {
+        2
+    };
+    let mid = (start + end) / 2;
+    items[start..end].select_nth_unstable_by(mid - start, |a, b| {
+        a.0.center()[axis].total_cmp(&b.0.center()[axis])
+    });
+    let left = build(items, start, mid, nodes);
+    let right = build(items, mid, end, nodes);
+    nodes[index] = Node::Branch { aabb, left, right };
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::vec3;
+
+    use super::*;
+
+    #[test]
+    fn test_bvh() {
+        // a 10 x 10 grid of unit boxes on the ground
+        let items = (0..100)
+            .map(|i| {
+                let min = vec3((i % 10) as f32 * 2.0, 0.0, (i / 10) as f32 * 2.0);
+                (Aabb::new(min, min + Vec3::ONE), i)
+            })
+            .collect::<Vec<_>>();
+        let bvh = Bvh::new(items.clone());
+        assert_eq!(bvh.len(), 100);
+
+        let mut found = bvh.query_point(vec3(4.5, 0.5, 6.5), 0.0);
+        assert_eq!(found, vec![&32]);
+        found = bvh.query_point(vec3(3.5, 0.5, 6.5), 0.6);
+        found.sort();
+        assert_eq!(found, vec![&31, &32]);
+
+        let area = Aabb::new(vec3(1.5, -1.0, 1.5), vec3(4.5, 1.0, 2.5));
+        let mut found = bvh.query_aabb(&area);
+        found.sort();
+        let mut expected = items
+            .iter()
+            .filter(|(aabb, _)| aabb.intersects(&area))
+            .map(|(_, i)| i)
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(found, expected);
+
+        // along the third row, nearest first
+        let hits = bvh.query_ray(vec3(-1.0, 0.5, 4.5), Vec3::X, 6.0);
+        assert_eq!(
+            hits.iter().map(|(_, i)| **i).collect::<Vec<_>>(),
+            vec![20, 21, 22]
+        );
+        assert_eq!(hits[0].0, 1.0);
+
+        // diagonally from the first box, stopping short of the third one
+        let mut found = bvh.query_segment(vec3(0.5, 0.5, 0.5), vec3(3.5, 0.5, 3.5), 0.0);
+        found.sort();
+        assert_eq!(found, vec![&0, &11]);
+        let mut found = bvh.query_segment(vec3(5.5, 0.5, 0.5), vec3(5.5, 0.5, 0.5), 0.6);
+        found.sort();
+        assert_eq!(found, vec![&2, &3]);
+        assert!(bvh
+            .query_ray(vec3(-1.0, 2.0, 4.5), Vec3::X, 100.0)
+            .is_empty());
+        assert!(Bvh::<usize>::default()
+            .query_point(Vec3::ZERO, 1.0)
+            .is_empty());
+    }
+}
@@ -1,2 +1,118 @@
-mod curve_pair;
+//! collision shapes of the road network, seen from above (y is ignored).
+//!
+//! every shape is covered by convex pieces; overlap and penetration run the separating axis
+//! test on each pair of pieces whose boxes meet.
 
+use bevy::math::Vec3;
+
+use self::hull::ConvexHull;
+
+use super::aabb::Aabb;
+
+pub mod curve_pair;
+pub mod hull;
+pub mod obb;
+
+/// how deep two shapes overlap
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Penetration {
+    pub depth: f32,
+    /// unit direction on the ground to push the other shape out of this one by |depth|
+    pub normal: Vec3,
+}
+
+pub trait Collider {
+    fn bbox(&self) -> Aabb;
+
+    /// convex pieces that together cover the shape
+    fn convex_parts(&self) -> Vec<ConvexHull>;
+
+    fn contains(&self, pt: Vec3) -> bool {
+        self.convex_parts().iter().any(|part| part.contains(pt))
+    }
+
+    /// whether every corner of |other| is inside self
+    fn encloses(&self, other: &impl Collider) -> bool {
+        other.convex_parts().iter().all(|part| {
+            part.points()
+                .iter()
+                .all(|p| self.contains(Vec3::new(p.x, 0.0, p.y)))
+        })
+    }
+
+    fn overlaps(&self, other: &impl Collider) -> bool {
+        self.penetration(other).is_some()
+    }
+
+    /// the deepest overlap between a piece of self and a piece of |other|, None if they are
+    /// apart. touching shapes overlap by 0.
+    fn penetration(&self, other: &impl Collider) -> Option<Penetration> {
+        if !self.bbox().intersects_xz(&other.bbox()) {
+            return None;
+        }
+        let theirs = other
+            .convex_parts()
+            .into_iter()
+            .map(|part| (part.bbox(), part))
+            .collect::<Vec<_>>();
+        self.convex_parts()
+            .iter()
+            .flat_map(|mine| {
+                let aabb = mine.bbox();
+                theirs
+                    .iter()
+                    .filter(move |(other_aabb, _)| aabb.intersects_xz(other_aabb))
+                    .filter_map(|(_, part)| mine.sat(part))
+                    .collect::<Vec<_>>()
+            })
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map(Penetration::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::vec3;
+
+    use super::{curve_pair::CurvePair, obb::Obb, *};
+    use crate::core::math::curve::line::LineSegment;
+
+    #[test]
+    fn test_colliders() {
+        let hull = ConvexHull::from_points([
+            vec3(0.0, 0.0, 0.0),
+            vec3(4.0, 1.0, 0.0),
+            vec3(2.0, 0.0, 1.0),
+            vec3(4.0, 0.0, 4.0),
+            vec3(0.0, 0.0, 4.0),
+        ]);
+        // (2, 1) is inside and dropped
+        assert_eq!(hull.points().len(), 4);
+        assert!(hull.contains(vec3(1.0, 5.0, 3.0)));
+        assert!(!hull.contains(vec3(5.0, 0.0, 3.0)));
+
+        // a car half way into the square, facing +x
+        let car = Obb::new(vec3(4.5, 0.0, 2.0), Vec3::X, 2.0, 1.0);
+        let penetration = hull.penetration(&car).unwrap();
+        assert!((penetration.depth - 0.5).abs() < 1e-5);
+        assert!((penetration.normal - Vec3::X).length() < 1e-5);
+        assert!(!hull.encloses(&car));
+        let parked = Obb::new(vec3(2.0, 0.0, 2.0), Vec3::Z, 2.0, 1.0);
+        assert!(hull.encloses(&parked));
+        let away = Obb::new(vec3(7.0, 0.0, 2.0), vec3(1.0, 0.0, 1.0), 2.0, 1.0);
+        assert!(!hull.overlaps(&away));
+
+        // a road 2 wide along z through the square
+        let road = CurvePair::from_center(
+            &LineSegment::new([vec3(2.0, 0.0, -5.0), vec3(2.0, 0.0, 10.0)]).to_curve(),
+            2.0,
+        );
+        assert!(road.overlaps(&hull));
+        assert!(road.contains(vec3(2.9, 0.0, 9.0)));
+        let beside = Obb::new(vec3(3.6, 0.0, 8.0), Vec3::Z, 2.0, 1.0);
+        assert!(!road.overlaps(&beside));
+        let on = Obb::new(vec3(3.4, 0.0, 8.0), Vec3::Z, 2.0, 1.0);
+        assert!((road.penetration(&on).unwrap().depth - 0.1).abs() < 1e-4);
+        assert!(road.encloses(&Obb::new(vec3(2.0, 0.0, 8.0), Vec3::Z, 2.0, 1.0)));
+    }
+}
@@ -0,0 +1,188 @@
+//! the area between two curves, like a road between its left and right edges, seen from
+//! above. the ends are closed by straight caps.
+
+use bevy::math::{Vec2, Vec3};
+
+use crate::core::math::{
+    aabb::Aabb,
+    curve::{line::LineSegment, Curve},
+    line::{Extent, Intersection, Line},
+};
+
+use super::{hull::ConvexHull, Collider};
+
+/// how far the outline used for point queries may be from the real edges
+const OUTLINE_TOLERANCE: f32 = 1e-2;
+
+#[derive(Debug, Clone)]
+pub struct CurvePair {
+    pub left: Curve,
+    pub right: Curve,
+    /// left edge forward then right edge backward, on the ground
+    outline: Vec<Vec2>,
+    /// quads between matching points of the edges
+    parts: Vec<ConvexHull>,
+    aabb: Aabb,
+}
+
+impl CurvePair {
+    /// |left| and |right| run the same way
+    pub fn new(left: Curve, right: Curve) -> Self {
+        let mut outline = left.to_polyline(OUTLINE_TOLERANCE);
+        let back = right.to_polyline(OUTLINE_TOLERANCE);
+        let n = (outline.len().max(back.len()) - 1).max(1);
+        outline.extend(back.into_iter().rev());
+        let parts = (0..n)
+            .map(|i| {
+                let (t0, t1) = (i as f32 / n as f32, (i + 1) as f32 / n as f32);
+                ConvexHull::from_points([
+                    left.position(t0),
+                    left.position(t1),
+                    right.position(t1),
+                    right.position(t0),
+                ])
+            })
+            .collect();
+        Self {
+            aabb: left.bbox().union(&right.bbox()),
+            outline: outline.into_iter().map(|p| Vec2::new(p.x, p.z)).collect(),
+            parts,
+            left,
+            right,
+        }
+    }
+
+    /// the band |width| wide around |center|
+    pub fn from_center(center: &Curve, width: f32) -> Self {
+        Self::new(
+            center.offset(-width / 2.0, 0.0),
+            center.offset(width / 2.0, 0.0),
+        )
+    }
+
+    /// the straight ends, at the start and at the end
+    fn caps(&self) -> [LineSegment; 2] {
+        [
+            LineSegment::new([self.left.start(), self.right.start()]),
+            LineSegment::new([self.left.end(), self.right.end()]),
+        ]
+    }
+
+    /// the edges and the two caps
+    fn boundary(&self) -> Vec<Curve> {
+        let [start, end] = self.caps();
+        vec![
+            self.left.clone(),
+            self.right.clone(),
+            start.to_curve(),
+            end.to_curve(),
+        ]
+    }
+
+    /// whether |line| touches the area, ignoring y
+    pub fn intersects_line(&self, line: &impl Line) -> bool {
+        self.contains(line.p())
+            || (line.extent() == Extent::Segment && self.contains(line.q()))
+            || [&self.left, &self.right]
+                .iter()
+                .any(|edge| !line.intersections_with_curve(edge).is_empty())
+            || self
+                .caps()
+                .iter()
+                .any(|cap| cap.intersection_xz(line) != Intersection::None)
+    }
+
+    /// whether the two areas overlap, ignoring y
+    pub fn intersects(&self, other: &CurvePair) -> bool {
+        if !self.aabb.intersects_xz(&other.aabb) {
+            return false;
+        }
+        if self.contains(other.left.start()) || other.contains(self.left.start()) {
+            return true;
+        }
+        let theirs = other.boundary();
+        self.boundary().iter().any(|mine| {
+            theirs
+                .iter()
+                .any(|edge| !mine.intersections(edge).is_empty())
+        })
+    }
+}
+
+impl Collider for CurvePair {
+    fn bbox(&self) -> Aabb {
+        self.aabb
+    }
+
+    fn convex_parts(&self) -> Vec<ConvexHull> {
+        self.parts.clone()
+    }
+
+    /// whether |pt| is inside, ignoring y
+    fn contains(&self, pt: Vec3) -> bool {
+        let pt = Vec2::new(pt.x, pt.z);
+        let min = Vec2::new(self.aabb.min.x, self.aabb.min.z);
+        let max = Vec2::new(self.aabb.max.x, self.aabb.max.z);
+        if pt.cmplt(min).any() || pt.cmpgt(max).any() {
+            return false;
+        }
+        // even-odd rule
+        let n = self.outline.len();
+        (0..n).fold(false, |inside, i| {
+            let (a, b) = (self.outline[i], self.outline[(i + 1) % n]);
+            if (a.y > pt.y) != (b.y > pt.y) && pt.x < a.x + (pt.y - a.y) / (b.y - a.y) * (b.x - a.x)
+            {
+                !inside
+            } else {
+                inside
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::vec3;
+
+    use super::*;
+    use crate::core::math::{curve::arc::ArcSegment, line::LineByPQ};
+
+    #[test]
+    fn test_curve_pair() {
+        // a road 2 wide along x from 0 to 10
+        let center = LineSegment::new([vec3(0.0, 0.0, 0.0), vec3(10.0, 0.0, 0.0)]).to_curve();
+        let road = CurvePair::from_center(&center, 2.0);
+        assert!(road.contains(vec3(5.0, 3.0, 0.9)));
+        assert!(!road.contains(vec3(5.0, 0.0, 1.1)));
+        assert!(!road.contains(vec3(-0.1, 0.0, 0.0)));
+
+        assert!(road.intersects_line(&LineByPQ::segment(
+            vec3(5.0, 0.0, -3.0),
+            vec3(5.0, 0.0, 3.0)
+        )));
+        assert!(road.intersects_line(&LineByPQ::segment(vec3(5.0, 0.0, 0.0), vec3(6.0, 0.0, 0.0))));
+        assert!(road.intersects_line(&LineByPQ::ray(vec3(-5.0, 0.0, 0.5), vec3(1.0, 0.0, 0.0))));
+        assert!(!road.intersects_line(&LineByPQ::segment(
+            vec3(-5.0, 0.0, 0.5),
+            vec3(-1.0, 0.0, 0.5)
+        )));
+
+        // a quarter turn of radius 5 starting at (12, 0, -5), bending away from the road
+        let arc =
+            ArcSegment::new(vec3(12.0, 0.0, 0.0), vec3(0.0, 0.0, -5.0), Vec3::Y, -1.5).to_curve();
+        let turn = CurvePair::from_center(&arc, 2.0);
+        assert!(!road.intersects(&turn));
+        let crossing = CurvePair::from_center(
+            &LineSegment::new([vec3(5.0, 0.0, -5.0), vec3(5.0, 0.0, 5.0)]).to_curve(),
+            1.0,
+        );
+        assert!(road.intersects(&crossing));
+        // fully inside, no edges cross
+        let inside = CurvePair::from_center(
+            &LineSegment::new([vec3(2.0, 0.0, 0.0), vec3(4.0, 0.0, 0.0)]).to_curve(),
+            0.5,
+        );
+        assert!(road.intersects(&inside));
+        assert!(inside.intersects(&road));
+    }
+}
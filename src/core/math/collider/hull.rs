@@ -0,0 +1,143 @@
+//! convex polygons on the ground plane, the building block of every collider
+
+use bevy::math::{Vec2, Vec3};
+
+use crate::core::math::aabb::Aabb;
+
+use super::{Collider, Penetration};
+
+/// a convex polygon seen from above. points are (x, z), counter-clockwise in that plane.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvexHull {
+    points: Vec<Vec2>,
+}
+
+impl ConvexHull {
+    /// the convex hull of |points|, y is ignored
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        let mut pts = points
+            .into_iter()
+            .map(|p| Vec2::new(p.x, p.z))
+            .collect::<Vec<_>>();
+        pts.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+        pts.dedup_by(|a, b| (*a - *b).length_squared() < 1e-12);
+        if pts.len() < 3 {
+            return Self { points: pts };
+        }
+        // Andrew's monotone chain
+        let cross = |o: Vec2, a: Vec2, b: Vec2| (a - o).perp_dot(b - o);
+        let mut hull: Vec<Vec2> = vec![];
+        for pass in 0..2 {
+            let start = hull.len();
+            let iter: Box<dyn Iterator<Item = &Vec2>> = if pass == 0 {
+                Box::new(pts.iter())
+            } else {
+                Box::new(pts.iter().rev())
+            };
+            for p in iter {
+                while hull.len() >= start + 2
+                    && cross(hull[hull.len() - 2], hull[hull.len() - 1], *p) <= 0.0
+                {
+                    hull.pop();
+                }
+                hull.push(*p);
+            }
+            // the last point is the first of the other chain
+            hull.pop();
+        }
+        Self { points: hull }
+    }
+
+    pub fn points(&self) -> &[Vec2] {
+        &self.points
+    }
+
+    /// the same polygon moved by |offset|, y is ignored
+    pub fn translate(&self, offset: Vec3) -> Self {
+        let offset = Vec2::new(offset.x, offset.z);
+        Self {
+            points: self.points.iter().map(|p| *p + offset).collect(),
+        }
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        let n = self.points.len();
+        (0..n).map(move |i| (self.points[i], self.points[(i + 1) % n]))
+    }
+
+    fn center(&self) -> Vec2 {
+        self.points.iter().copied().sum::<Vec2>() / self.points.len().max(1) as f32
+    }
+
+    /// the range of the points projected on |axis|
+    fn project(&self, axis: Vec2) -> (f32, f32) {
+        self.points
+            .iter()
+            .map(|p| p.dot(axis))
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), d| {
+                (lo.min(d), hi.max(d))
+            })
+    }
+
+    /// separating axis test. the overlap depth and the unit axis pointing from self to
+    /// |other|, None if they are apart
+    pub fn sat(&self, other: &ConvexHull) -> Option<(f32, Vec2)> {
+        if self.points.is_empty() || other.points.is_empty() {
+            return None;
+        }
+        let axes = self
+            .edges()
+            .chain(other.edges())
+            .filter_map(|(a, b)| (b - a).perp().try_normalize())
+            .collect::<Vec<_>>();
+        let towards = other.center() - self.center();
+        if axes.is_empty() {
+            // both are single points
+            return ((towards).length() < 1e-6).then_some((0.0, Vec2::X));
+        }
+        let mut best = (f32::INFINITY, Vec2::X);
+        for axis in axes {
+            let (a_lo, a_hi) = self.project(axis);
+            let (b_lo, b_hi) = other.project(axis);
+            let overlap = (a_hi - b_lo).min(b_hi - a_lo);
+            if overlap < 0.0 {
+                return None;
+            }
+            if overlap < best.0 {
+                let axis = if axis.dot(towards) < 0.0 { -axis } else { axis };
+                best = (overlap, axis);
+            }
+        }
+        Some(best)
+    }
+}
+
+impl Collider for ConvexHull {
+    fn bbox(&self) -> Aabb {
+        Aabb::from_points(self.points.iter().map(|p| Vec3::new(p.x, 0.0, p.y)))
+    }
+
+    fn convex_parts(&self) -> Vec<ConvexHull> {
+        vec![self.clone()]
+    }
+
+    fn contains(&self, pt: Vec3) -> bool {
+        let pt = Vec2::new(pt.x, pt.z);
+        match self.points.len() {
+            0 => false,
+            1 => (self.points[0] - pt).length() < 1e-6,
+            _ => self
+                .edges()
+                .all(|(a, b)| (b - a).perp_dot(pt - a) >= -1e-6 * (b - a).length()),
+        }
+    }
+}
+
+impl From<(f32, Vec2)> for Penetration {
+    fn from((depth, axis): (f32, Vec2)) -> Self {
+        Self {
+            depth,
+            normal: Vec3::new(axis.x, 0.0, axis.y),
+        }
+    }
+}
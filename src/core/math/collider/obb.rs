@@ -0,0 +1,60 @@
+//! oriented boxes, e.g. the footprint of a car
+
+use bevy::math::{vec3, Vec3};
+
+use crate::core::math::aabb::Aabb;
+
+use super::{hull::ConvexHull, Collider};
+
+/// a rectangle on the ground turned to face |heading|
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Obb {
+    pub center: Vec3,
+    /// unit direction of the long side, on the ground
+    pub heading: Vec3,
+    pub half_length: f32,
+    pub half_width: f32,
+}
+
+impl Obb {
+    pub fn new(center: Vec3, heading: Vec3, length: f32, width: f32) -> Self {
+        Self {
+            center,
+            heading: vec3(heading.x, 0.0, heading.z).normalize_or_zero(),
+            half_length: length / 2.0,
+            half_width: width / 2.0,
+        }
+    }
+
+    /// front right, front left, back left, back right
+    pub fn corners(&self) -> [Vec3; 4] {
+        let forward = self.heading * self.half_length;
+        let right = vec3(-self.heading.z, 0.0, self.heading.x) * self.half_width;
+        [
+            self.center + forward + right,
+            self.center + forward - right,
+            self.center - forward - right,
+            self.center - forward + right,
+        ]
+    }
+
+    pub fn to_hull(&self) -> ConvexHull {
+        ConvexHull::from_points(self.corners())
+    }
+}
+
+impl Collider for Obb {
+    fn bbox(&self) -> Aabb {
+        Aabb::from_points(self.corners())
+    }
+
+    fn convex_parts(&self) -> Vec<ConvexHull> {
+        vec![self.to_hull()]
+    }
+
+    fn contains(&self, pt: Vec3) -> bool {
+        let d = pt - self.center;
+        let right = vec3(-self.heading.z, 0.0, self.heading.x);
+        d.dot(self.heading).abs() <= self.half_length && d.dot(right).abs() <= self.half_width
+    }
+}
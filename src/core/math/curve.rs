@@ -1,47 +1,359 @@
-use std::cmp::min;
-use anyhow::{anyhow, Result};
-
 use bevy::math::{vec3, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::core::math::{
+    aabb::Aabb,
+    error::{check_finite, GeometryError, Result},
+};
 
-use self::quadratic::QuadraticBezierCurve;
+use self::{
+    arc::ArcSegment,
+    arc_length::ArcLengthTable,
+    clothoid::ClothoidSegment,
+    cubic::CubicBezierCurve,
+    join::Fillet,
+    line::LineSegment,
+    offset::WidthProfile,
+    profile::VerticalProfile,
+    quadratic::QuadraticBezierCurve,
+    segment::{FrenetFrame, Segment},
+};
 
+pub mod arc;
+mod arc_length;
+pub mod clothoid;
+pub mod cubic;
+pub mod export;
+mod intersect;
+pub mod join;
+pub mod line;
+pub mod offset;
+pub mod placed;
+pub mod profile;
 pub mod quadratic;
+pub mod segment;
+
+/// the closest point on a curve to a query point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Projection {
+    /// index of the segment
+    pub index: usize,
+    /// t on that segment
+    pub t: f32,
+    /// arc length from the start of the curve
+    pub length: f32,
+    pub distance: f32,
+}
+
+/// how smoothly two curves must meet to be joined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Continuity {
+    /// the end points meet
+    G0,
+    /// the end points meet and the directions agree
+    G1,
+}
+
+/// a single piece of a [`Curve`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CurveSegment {
+    Line(LineSegment),
+    Arc(ArcSegment),
+    Clothoid(ClothoidSegment),
+    Quadratic(QuadraticBezierCurve),
+    Cubic(CubicBezierCurve),
+}
+
+impl From<LineSegment> for CurveSegment {
+    fn from(curve: LineSegment) -> Self {
+        Self::Line(curve)
+    }
+}
+
+impl From<ArcSegment> for CurveSegment {
+    fn from(curve: ArcSegment) -> Self {
+        Self::Arc(curve)
+    }
+}
+
+impl From<ClothoidSegment> for CurveSegment {
+    fn from(curve: ClothoidSegment) -> Self {
+        Self::Clothoid(curve)
+    }
+}
+
+impl From<QuadraticBezierCurve> for CurveSegment {
+    fn from(curve: QuadraticBezierCurve) -> Self {
+        Self::Quadratic(curve)
+    }
+}
+
+impl From<CubicBezierCurve> for CurveSegment {
+    fn from(curve: CubicBezierCurve) -> Self {
+        Self::Cubic(curve)
+    }
+}
+
+macro_rules! dispatch {
+    ($self:ident, $c:ident => $e:expr) => {
+        match $self {
+            CurveSegment::Line($c) => $e,
+            CurveSegment::Arc($c) => $e,
+            CurveSegment::Clothoid($c) => $e,
+            CurveSegment::Quadratic($c) => $e,
+            CurveSegment::Cubic($c) => $e,
+        }
+    };
+}
+
+impl Segment for CurveSegment {
+    fn position(&self, t: f32) -> Vec3 {
+        dispatch!(self, c => c.position(t))
+    }
+
+    fn tangent(&self, t: f32) -> Vec3 {
+        dispatch!(self, c => c.tangent(t))
+    }
+
+    fn acceleration(&self, t: f32) -> Vec3 {
+        dispatch!(self, c => c.acceleration(t))
+    }
 
-#[derive(Debug, Clone)]
+    fn length_of(&self, t: f32) -> f32 {
+        dispatch!(self, c => c.length_of(t))
+    }
+
+    fn split_at_t(&self, t: f32) -> (Self, Self) {
+        dispatch!(self, c => {
+            let (a, b) = c.split_at_t(t);
+            (a.into(), b.into())
+        })
+    }
+
+    fn reverse(&self) -> Self {
+        dispatch!(self, c => c.reverse().into())
+    }
+
+    fn translate(&self, offset: Vec3) -> Self {
+        dispatch!(self, c => c.translate(offset).into())
+    }
+
+    fn bbox(&self) -> Aabb {
+        dispatch!(self, c => c.bbox())
+    }
+
+    fn closest_t(&self, pt: Vec3) -> f32 {
+        dispatch!(self, c => c.closest_t(pt))
+    }
+}
+
+impl CurveSegment {
+    /// n + 1 points at evenly spaced t, both ends included
+    pub fn iter_positions(&self, n: isize) -> impl Iterator<Item = Vec3> + '_ {
+        let n = n.max(1);
+        (0..=n).map(move |i| self.position(i as f32 / n as f32))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "SavedCurve", into = "SavedCurve")]
 pub struct Curve {
-    curves: Vec<QuadraticBezierCurve>,
+    curves: Vec<CurveSegment>,
     // the prefix sum of the lengths of each curve
     sum_lengths: Vec<f32>,
+    // arc length -> (segment, t), so that position doesn't need to invert length_of
+    arc_table: ArcLengthTable,
+    tolerance: f32,
+}
+
+/// the part of a [`Curve`] that is saved, the lengths and lookup tables are rebuilt on load
+#[derive(Serialize, Deserialize)]
+struct SavedCurve {
+    curves: Vec<CurveSegment>,
+    tolerance: f32,
+}
+
+impl TryFrom<SavedCurve> for Curve {
+    type Error = GeometryError;
+
+    fn try_from(saved: SavedCurve) -> Result<Self> {
+        Ok(Self::try_from_segments(saved.curves)?.with_tolerance(saved.tolerance))
+    }
+}
+
+impl From<Curve> for SavedCurve {
+    fn from(curve: Curve) -> Self {
+        Self {
+            curves: curve.curves,
+            tolerance: curve.tolerance,
+        }
+    }
 }
 
 impl Curve {
+    /// default max error (in meters) of [`Self::position`] and [`Self::position_at_length`]
+    pub const DEFAULT_TOLERANCE: f32 = 1e-3;
+
     pub fn from_curves(curves: Vec<QuadraticBezierCurve>) -> Self {
+        Self::from_segments(curves.into_iter().map(CurveSegment::Quadratic).collect())
+    }
+
+    /// panics on an empty or non-finite chain, see [`Self::try_from_segments`]
+    pub fn from_segments(curves: Vec<CurveSegment>) -> Self {
+        match Self::try_from_segments(curves) {
+            Ok(curve) => curve,
+            Err(err) => panic!("invalid curve: {}", err),
+        }
+    }
+
+    /// a curve through |curves| one after another. fails if there are none or a control
+    /// point isn't finite.
+    pub fn try_from_segments(curves: Vec<CurveSegment>) -> Result<Self> {
+        if curves.is_empty() {
+            return Err(GeometryError::EmptyCurve);
+        }
+        for curve in curves.iter() {
+            check_finite("curve segment", &[curve.start(), curve.end()])?;
+        }
+        Ok(Self::build(curves, Self::DEFAULT_TOLERANCE))
+    }
+
+    /// |curves| are never empty, every constructor keeps at least one segment
+    fn build(curves: Vec<CurveSegment>, tolerance: f32) -> Self {
+        debug_assert!(!curves.is_empty());
+        // summed in f64 so long chains don't drift
         let sum_lengths = curves
             .iter()
-            .map(|curve| curve.length())
+            .map(|curve| curve.length() as f64)
             .scan(0.0, |sum, x| {
                 *sum += x;
-                Some(*sum)
+                Some(*sum as f32)
             })
             .collect();
         Self {
+            arc_table: ArcLengthTable::new(&curves, tolerance),
+            tolerance,
             curves,
             sum_lengths,
         }
     }
+
+    /// rebuild the arc length table so that positions are off by at most |tolerance| meters.
+    /// smaller tolerances take more memory, lookups stay O(log n).
+    pub fn with_tolerance(mut self, tolerance: f32) -> Self {
+        self.arc_table = ArcLengthTable::new(&self.curves, tolerance);
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn tolerance(&self) -> f32 {
+        self.tolerance
+    }
+    /// the part between t = |start| and t = |end|
     pub fn slice(&self, start: f32, end: f32) -> Self {
-        let start_pt = self.position(start);
-        let end_pt = self.position(end);
-        self.split_at(start_pt).1.split_at(end_pt).0
+        self.between(start * self.length(), end * self.length())
     }
 
+    /// fails unless 0 <= |start| < |end| <= length, within the tolerance
     pub fn slice_by_length(&self, start: f32, end: f32) -> Result<Self> {
-        let start = start / self.length();
-        let end = end / self.length();
-        if start >= end {
-            return Err(anyhow!("start should be less than end"));
+        let length = self.length();
+        if !(start < end && start > -self.tolerance && end < length + self.tolerance) {
+            return Err(GeometryError::InvalidRange {
+                start,
+                end,
+                limit: length,
+            });
+        }
+        Ok(self.between(start, end))
+    }
+
+    /// the part between the arc lengths |start| and |end|. segments are cut at the exact
+    /// length instead of at a projected point.
+    fn between(&self, start: f32, end: f32) -> Self {
+        let (start, end) = (start.max(0.0), end.min(self.length()));
+        let mut curves = vec![];
+        for (index, curve) in self.curves.iter().enumerate() {
+            let from = if index == 0 {
+                0.
+            } else {
+                self.sum_lengths[index - 1]
+            };
+            let to = self.sum_lengths[index];
+            if to <= start || from >= end {
+                continue;
+            }
+            let mut piece = curve.clone();
+            if end < to {
+                piece = piece.split_at_t(curve.t_of_length(end - from)).0;
+            }
+            if start > from {
+                piece = piece.split_at_t(piece.t_of_length(start - from)).1;
+            }
+            curves.push(piece);
+        }
+        if curves.is_empty() {
+            // nothing left, keep the point
+            let (idx, t) = self.arc_table.locate(start);
+            let pt = self.curves[idx].position(t);
+            curves.push(LineSegment::new([pt, pt]).into());
+        }
+        Self::build(curves, self.tolerance)
+    }
+
+    /// the two parts before and after the arc length |length|
+    pub fn split_at_length(&self, length: f32) -> (Self, Self) {
+        (
+            self.between(0.0, length),
+            self.between(length, self.length()),
+        )
+    }
+
+    /// drop |length| meters from the start
+    pub fn trim_start(&self, length: f32) -> Result<Self> {
+        self.slice_by_length(length, self.length())
+    }
+
+    /// drop |length| meters from the end
+    pub fn trim_end(&self, length: f32) -> Result<Self> {
+        self.slice_by_length(0.0, self.length() - length)
+    }
+
+    /// the same curve driven the other way, e.g. for the opposite lane
+    pub fn reverse(&self) -> Self {
+        let curves = self.curves.iter().rev().map(|c| c.reverse()).collect();
+        Self::build(curves, self.tolerance)
+    }
+
+    /// the same curve moved by |offset|. lengths don't change, so nothing is rebuilt.
+    pub fn translate(&self, offset: Vec3) -> Self {
+        Self {
+            curves: self.curves.iter().map(|c| c.translate(offset)).collect(),
+            sum_lengths: self.sum_lengths.clone(),
+            arc_table: self.arc_table.clone(),
+            tolerance: self.tolerance,
+        }
+    }
+
+    /// |other| appended to self. fails if |other| doesn't start where self ends (within
+    /// the tolerance) or, for [`Continuity::G1`], doesn't leave in the same direction.
+    pub fn concat(&self, other: &Curve, continuity: Continuity) -> Result<Self> {
+        const MAX_ANGLE: f32 = 1e-3;
+        let gap = (other.start() - self.end()).length();
+        let angle = self.velocity(1.0).angle_between(other.velocity(0.0));
+        if gap > self.tolerance || (continuity == Continuity::G1 && angle > MAX_ANGLE) {
+            return Err(GeometryError::Discontinuous { gap, angle });
         }
-        Ok(self.slice(start, end))
+        let curves = self.curves.iter().chain(other.curves.iter()).cloned();
+        Ok(Self::build(curves.collect(), self.tolerance))
+    }
+
+    /// points evenly spaced along the curve, no more than |spacing| apart, including both
+    /// ends
+    pub fn resample(&self, spacing: f32) -> Vec<Vec3> {
+        let n = (self.length() / spacing).ceil().max(1.) as usize;
+        (0..=n)
+            .map(|i| self.position_at_length(self.length() * i as f32 / n as f32))
+            .collect()
     }
 
     pub fn form_two_velocity(p: Vec3, v: Vec3, q: Vec3, u: Vec3) -> Result<Self> {
@@ -50,22 +362,133 @@ impl Curve {
         let p1 = p + v.normalize() * len / 3.0;
         let p2 = q - u.normalize() * len / 3.0;
         let p3 = q;
-        if p1.is_nan() || p2.is_nan() || p0.is_nan() || p3.is_nan() {
-            Err(anyhow!("nan found in form_two_velocity, {:?}", (p0, p1, p2, p3)))
-        } else {
-            Ok(Self::from_4_points(p0, p1, p2, p3))
+        check_finite("form_two_velocity", &[p0, p1, p2, p3])?;
+        Ok(Self::from_4_points(p0, p1, p2, p3))
+    }
+
+    /// build a road alignment from |p| heading |v| to |q| heading |u| on the ground plane:
+    /// straight - clothoid - arc - clothoid - straight.
+    ///
+    /// |spiral_ratio| in (0, 1] is the part of the total deflection taken by the two
+    /// clothoids. the radius is chosen as large as the distances to the tangent intersection
+    /// allow, and the remaining distance on the longer side becomes a straight segment.
+    pub fn from_clothoid_alignment(
+        p: Vec3,
+        v: Vec3,
+        q: Vec3,
+        u: Vec3,
+        spiral_ratio: f32,
+    ) -> Result<Self> {
+        const EPS: f32 = 1e-4;
+        let d0 = vec3(v.x, 0.0, v.z).normalize_or_zero();
+        let d1 = vec3(u.x, 0.0, u.z).normalize_or_zero();
+        check_finite("clothoid alignment", &[p, v, q, u])?;
+        if d0 == Vec3::ZERO || d1 == Vec3::ZERO {
+            return Err(GeometryError::Degenerate {
+                what: "direction, vertical or zero",
+                points: vec![v, u],
+            });
+        }
+        if !(spiral_ratio > 0.0 && spiral_ratio <= 1.0) {
+            return Err(GeometryError::InvalidArgument {
+                name: "spiral_ratio",
+                value: spiral_ratio,
+            });
+        }
+        let w = q - p;
+        let cross = d0.x * d1.z - d0.z * d1.x;
+        if cross.abs() < EPS {
+            // parallel tangents only work if q is straight ahead
+            let ahead = w.dot(d0);
+            if d0.dot(d1) > 0.0 && ahead > 0.0 && (w - d0 * ahead).length() < EPS {
+                return Ok(LineSegment::new([p, q]).to_curve());
+            }
+            return Err(GeometryError::NoSolution("crossing of parallel tangents"));
+        }
+        // solve p + a * d0 = q - b * d1 for the tangent intersection
+        let a = (w.x * d1.z - w.z * d1.x) / cross;
+        let b = (d0.x * w.z - d0.z * w.x) / cross;
+        if a <= EPS || b <= EPS {
+            return Err(GeometryError::NoSolution("crossing ahead of both tangents"));
+        }
+        let sign = cross.signum();
+        let deflection = d0.angle_between(d1);
+        let theta_s = spiral_ratio * deflection / 2.0;
+
+        // for a fixed spiral angle, the whole shape scales with the radius. so compute the
+        // tangent length for a unit radius and scale it to fit.
+        let unit_spiral = ClothoidSegment::new(Vec3::ZERO, 0.0, 0.0, 1.0, 2.0 * theta_s);
+        let spiral_end = unit_spiral.end();
+        let shift = spiral_end.z - (1.0 - theta_s.cos());
+        let abscissa = spiral_end.x - theta_s.sin();
+        let unit_tangent = (1.0 + shift) * (deflection / 2.0).tan() + abscissa;
+        let tangent_len = a.min(b);
+        let radius = tangent_len / unit_tangent;
+
+        let heading = d0.z.atan2(d0.x);
+        let spiral_len = 2.0 * radius * theta_s;
+        let mut segments: Vec<CurveSegment> = vec![];
+        let spiral_start = p + d0 * (a - tangent_len);
+        if a - tangent_len > EPS {
+            segments.push(LineSegment::new([p, spiral_start]).into());
+        }
+        let spiral_in = ClothoidSegment::new(spiral_start, heading, 0.0, sign / radius, spiral_len);
+        let arc_start = spiral_in.end();
+        segments.push(spiral_in.into());
+
+        let arc_sweep = deflection - 2.0 * theta_s;
+        let arc_heading = heading + sign * theta_s;
+        let mut spiral_out_start = arc_start;
+        if arc_sweep > EPS {
+            // positive curvature turns towards +z, which is a rotation around -Y
+            let normal = -Vec3::Y * sign;
+            let dir = vec3(arc_heading.cos(), 0.0, arc_heading.sin());
+            let from = dir.cross(normal) * radius;
+            let arc = ArcSegment::new(arc_start - from, from, normal, arc_sweep);
+            spiral_out_start = arc.end();
+            segments.push(arc.into());
+        }
+        let spiral_out = ClothoidSegment::new(
+            spiral_out_start,
+            arc_heading + sign * arc_sweep,
+            sign / radius,
+            0.0,
+            spiral_len,
+        );
+        let spiral_end = spiral_out.end();
+        segments.push(spiral_out.into());
+        if (q - spiral_end).length() > EPS {
+            segments.push(LineSegment::new([spiral_end, q]).into());
         }
+        Ok(Self::from_segments(segments))
     }
 
-    // construct a curve that smoothly connects the two curves from 4 points
+    /// round the corner where |a| crosses |b| with an arc of |radius| tangent to both,
+    /// keeping |a| before the arc and |b| after it
+    pub fn fillet(a: &Curve, b: &Curve, radius: f32) -> Result<Fillet> {
+        join::fillet(a, b, radius)
+    }
+
+    /// a tangent-continuous curve from the end of |a| to the start of |b| that never turns
+    /// tighter than |min_radius|
+    pub fn tangent_join(a: &Curve, b: &Curve, min_radius: f32) -> Result<Self> {
+        Self::from_tangents(
+            a.end(),
+            a.velocity(1.0),
+            b.start(),
+            b.velocity(0.0),
+            min_radius,
+        )
+    }
+
+    /// like [`Self::tangent_join`] from |p| heading |v| to |q| heading |u|
+    pub fn from_tangents(p: Vec3, v: Vec3, q: Vec3, u: Vec3, min_radius: f32) -> Result<Self> {
+        join::from_tangents(p, v, q, u, min_radius)
+    }
+
+    // construct a cubic Bézier curve from 4 control points
     pub fn from_4_points(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3) -> Self {
-        let mid = (p1 + p2) / 2.0;
-        let curve1 = QuadraticBezierCurve::new([p0, p1, mid]);
-        let curve2 = QuadraticBezierCurve::new([mid, p2, p3]);
-        Self {
-            sum_lengths: vec![curve1.length(), curve1.length() + curve2.length()],
-            curves: vec![curve1, curve2],
-        }
+        CubicBezierCurve::new([p0, p1, p2, p3]).to_curve()
     }
 
     pub fn start(&self) -> Vec3 {
@@ -73,152 +496,757 @@ impl Curve {
     }
 
     pub fn end(&self) -> Vec3 {
-        self.curves.last().unwrap().end()
+        self.curves[self.curves.len() - 1].end()
     }
 
-    pub fn distance_to(&self, pt: Vec3) -> f32 {
+    /// find the closest point on the curve to |pt|
+    pub fn project(&self, pt: Vec3) -> Projection {
         self.curves
             .iter()
-            .map(|curve| curve.distance_to(pt))
-            .min_by(|a, b| a.partial_cmp(b).unwrap())
-            .unwrap_or(f32::MAX)
+            .enumerate()
+            .map(|(index, curve)| {
+                let prefix_len = if index == 0 {
+                    0.
+                } else {
+                    self.sum_lengths[index - 1]
+                };
+                let projection = curve.project(pt);
+                Projection {
+                    index,
+                    length: prefix_len + projection.length,
+                    ..projection
+                }
+            })
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+            .unwrap_or(Projection {
+                index: 0,
+                t: 0.,
+                length: 0.,
+                distance: f32::MAX,
+            })
     }
 
-    // two new curve and the t value
-    pub fn split_at(&self, pt: Vec3) -> (Self, Self) {
-        // determine the curve to split
-        let mut idx: usize = 0;
-        let mut min_dist = f32::MAX;
-        for (i, curve) in self.curves.iter().enumerate() {
-            let dist = curve.distance_to(pt);
-            if dist < min_dist {
-                min_dist = dist;
-                idx = i;
+    pub fn distance_to(&self, pt: Vec3) -> f32 {
+        self.project(pt).distance
+    }
+
+    /// the t (in (0, 1), as in [`Self::position`]) of the point at |index| segment and |t|
+    /// on that segment
+    fn t_of_segment(&self, index: usize, t: f32) -> f32 {
+        let prefix_len = if index == 0 {
+            0.
+        } else {
+            self.sum_lengths[index - 1]
+        };
+        (prefix_len + self.curves[index].length_of(t)) / self.length()
+    }
+
+    /// all crossings with |other| on the ground plane (y is ignored), sorted along self.
+    /// touching curves count as a crossing.
+    ///
+    /// return (t on self, t on other, position on self)
+    pub fn intersections(&self, other: &Curve) -> Vec<(f32, f32, Vec3)> {
+        // neighboring pieces report the same crossing (or a touching stretch) several times.
+        // hits are merged if the curves don't separate by more than this between them
+        const MERGE_DIST: f32 = 1e-2;
+        let gap_on_ground = |a: Vec3, b: Vec3| vec3(a.x - b.x, 0.0, a.z - b.z).length();
+        let mut hits = intersect::intersections(&self.curves, &other.curves);
+        hits.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        let mut clusters: Vec<Vec<(usize, f32, usize, f32)>> = vec![];
+        for hit in hits {
+            if let Some(last) = clusters.last_mut().and_then(|c| c.last()) {
+                let (ia, ta) = (last.0, last.1);
+                let mid = if ia == hit.0 {
+                    self.curves[ia].position((ta + hit.1) / 2.0)
+                } else {
+                    self.curves[hit.0].start()
+                };
+                let mid_on_other = other.project(mid);
+                let other_mid = other.curves[mid_on_other.index].position(mid_on_other.t);
+                if gap_on_ground(mid, other_mid) < MERGE_DIST {
+                    clusters.last_mut().unwrap().push(hit);
+                    continue;
+                }
             }
+            clusters.push(vec![hit]);
         }
+        clusters
+            .into_iter()
+            .filter_map(|cluster| {
+                // the closest approach in the cluster
+                cluster
+                    .into_iter()
+                    .map(|(ia, ta, ib, tb)| {
+                        let pt = self.curves[ia].position(ta);
+                        let gap = gap_on_ground(pt, other.curves[ib].position(tb));
+                        (
+                            self.t_of_segment(ia, ta),
+                            other.t_of_segment(ib, tb),
+                            pt,
+                            gap,
+                        )
+                    })
+                    .min_by(|a, b| a.3.total_cmp(&b.3))
+                    .map(|(ta, tb, pt, _)| (ta, tb, pt))
+            })
+            .collect()
+    }
 
-        let (mut curve1, curve2) = self.curves[idx].split_at(pt);
+    // two new curve split at the point closest to pt
+    pub fn split_at(&self, pt: Vec3) -> (Self, Self) {
+        let Projection { index: idx, t, .. } = self.project(pt);
+        let (curve1, curve2) = self.curves[idx].split_at_t(t);
         let mut curves_1 = self.curves[..idx].to_vec();
-        curves_1.append(&mut curve1.curves);
-        let mut curves_2 = curve2.curves;
+        curves_1.push(curve1);
+        let mut curves_2 = vec![curve2];
         curves_2.extend_from_slice(&self.curves[idx + 1..]);
-        (Self::from_curves(curves_1), Self::from_curves(curves_2))
+        (
+            Self::build(curves_1, self.tolerance),
+            Self::build(curves_2, self.tolerance),
+        )
     }
 
     pub fn length(&self) -> f32 {
-        self.sum_lengths.last().unwrap().clone()
+        self.sum_lengths[self.sum_lengths.len() - 1]
+    }
+
+    pub fn bbox(&self) -> Aabb {
+        self.curves
+            .iter()
+            .fold(Aabb::empty(), |aabb, curve| aabb.union(&curve.bbox()))
     }
+    /// n + 1 points per segment at evenly spaced t. [`Self::to_polyline`] adapts to the
+    /// shape instead.
     pub fn iter_positions(&self, n: isize) -> impl Iterator<Item = Vec3> + '_ {
         self.curves
             .iter()
             .flat_map(move |curve| curve.iter_positions(n))
     }
 
+    /// vertices of a polyline within |tolerance| meters of the curve
+    pub fn to_polyline(&self, tolerance: f32) -> Vec<Vec3> {
+        export::polyline(self, tolerance)
+    }
+
+    /// [`Self::to_polyline`] with the arc length of each vertex, to look up the frame there
+    pub fn flatten(&self, tolerance: f32) -> Vec<(f32, Vec3)> {
+        export::flatten(self, tolerance)
+    }
+
+    /// an SVG path (the `d` attribute) of the curve seen from above, x to the right and z
+    /// downwards
+    pub fn to_svg_path(&self) -> String {
+        export::svg_path(self)
+    }
+
     // get the position of the curve at t. t is in (0, 1)
     pub fn position(&self, t: f32) -> Vec3 {
-        let l = t * self.length();
-        let idx = self
-            .sum_lengths
-            .binary_search_by(|&sum_length| {
-                sum_length
-                    .partial_cmp(&l)
-                    .unwrap_or(std::cmp::Ordering::Less)
+        self.position_at_length(t * self.length())
+    }
+
+    /// the position at |length| along the curve, within [`Self::tolerance`]
+    pub fn position_at_length(&self, length: f32) -> Vec3 {
+        let (idx, t) = self.arc_table.locate(length);
+        self.curves[idx].position(t)
+    }
+
+    /// the segment index and the t on that segment of the point at t of the whole curve
+    fn locate(&self, t: f32) -> (usize, f32) {
+        self.arc_table.locate(t * self.length())
+    }
+
+    /// the direction of travel at t. t is proportional to the arc length, so this is the
+    /// derivative of the position with respect to the arc length, a unit vector.
+    pub fn velocity(&self, t: f32) -> Vec3 {
+        let (idx, t) = self.locate(t);
+        self.curves[idx].unit_tangent(t)
+    }
+
+    /// signed curvature at t, positive when turning to the right hand side
+    pub fn curvature(&self, t: f32) -> f32 {
+        let (idx, t) = self.locate(t);
+        self.curves[idx].curvature(t)
+    }
+
+    /// tangent, normal, binormal and curvature at t
+    pub fn frenet(&self, t: f32) -> FrenetFrame {
+        let (idx, t) = self.locate(t);
+        self.curves[idx].frenet(t)
+    }
+
+    pub fn frenet_at_length(&self, length: f32) -> FrenetFrame {
+        self.frenet(length / self.length())
+    }
+
+    /// the parallel curve |right| meters to the right and |top| meters up, within
+    /// [`Self::tolerance`] of the exact offset
+    pub fn offset(&self, right: f32, top: f32) -> Self {
+        self.offset_with(&WidthProfile::constant(right), top, self.tolerance)
+    }
+
+    /// offset by a width that changes along the curve, e.g. a lane that tapers at a merge.
+    /// the result is within |tolerance| meters of the exact offset.
+    pub fn offset_with(&self, profile: &WidthProfile, top: f32, tolerance: f32) -> Self {
+        let rets = self
+            .curves
+            .iter()
+            .enumerate()
+            .flat_map(|(index, curve)| {
+                let prefix_len = if index == 0 {
+                    0.
+                } else {
+                    self.sum_lengths[index - 1]
+                };
+                offset::offset_segment(curve, prefix_len, profile, top, tolerance)
             })
-            .unwrap_or_else(|idx| min(idx, self.sum_lengths.len() - 1));
-        let prefix_len = if idx == 0 {
-            0.
-        } else {
-            self.sum_lengths[idx - 1]
-        };
-        // iter over length_of curve
-        let mut low = 0;
-        let mut high = 1024;
-        while low < high {
-            let mid = (low + high) / 2;
-            let t = mid as f32 / 1024.0;
-            let len = self.curves[idx].length_of(t);
-            if len < l - prefix_len {
-                low = mid + 1;
-            } else {
-                high = mid;
+            .collect();
+        Self::build(rets, self.tolerance)
+    }
+
+    /// lay the curve on |profile|, e.g. to build a ramp or a hill. self is the plan on the
+    /// ground: its arc length is the station and its own heights are dropped. the result is
+    /// within [`Self::tolerance`] of the exact 3D curve.
+    pub fn with_profile(&self, profile: &VerticalProfile) -> Self {
+        let rets = self
+            .curves
+            .iter()
+            .enumerate()
+            .flat_map(|(index, curve)| {
+                let prefix_len = if index == 0 {
+                    0.
+                } else {
+                    self.sum_lengths[index - 1]
+                };
+                profile::drape_segment(curve, prefix_len, profile, self.tolerance)
+            })
+            .collect();
+        Self::build(rets, self.tolerance)
+    }
+
+    /// rise over run at t, positive uphill
+    pub fn grade(&self, t: f32) -> f32 {
+        let v = self.velocity(t);
+        let run = vec3(v.x, 0.0, v.z).length();
+        if run < 1e-9 {
+            return v.y.signum() * f32::INFINITY;
+        }
+        v.y / run
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    use bevy::math::vec3;
+
+    use super::*;
+
+    #[test]
+    fn test_frenet() {
+        let arc = ArcSegment::from_tangent(
+            vec3(0.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(4.0, 0.0, 4.0),
+        )
+        .unwrap();
+        let curve = Curve::from_segments(vec![
+            LineSegment::new([vec3(-4.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0)]).into(),
+            arc.into(),
+        ]);
+        // on the straight part
+        let frame = curve.frenet(0.1);
+        assert!((frame.tangent - vec3(1.0, 0.0, 0.0)).length() < 1e-5);
+        assert!((frame.normal - vec3(0.0, 0.0, 1.0)).length() < 1e-5);
+        assert!((frame.binormal - vec3(0.0, -1.0, 0.0)).length() < 1e-5);
+        assert_eq!(frame.curvature, 0.0);
+
+        // on the arc, radius 4 turning towards +z
+        let frame = curve.frenet_at_length(4.0 + PI);
+        assert!((frame.curvature - 0.25).abs() < 1e-4, "{:?}", frame);
+        let center = vec3(0.0, 0.0, 4.0);
+        assert!((frame.position + frame.normal * 4.0 - center).length() < 1e-3);
+        assert!(frame.tangent.dot(frame.normal).abs() < 1e-5);
+        assert!((curve.velocity(1.0) - vec3(0.0, 0.0, 1.0)).length() < 1e-4);
+
+        // the same turn as a quadratic and a clothoid agree on the sign
+        let quadratic = QuadraticBezierCurve::new([
+            vec3(0.0, 0.0, 0.0),
+            vec3(4.0, 0.0, 0.0),
+            vec3(4.0, 0.0, 4.0),
+        ]);
+        assert!(quadratic.curvature(0.5) > 0.0);
+        let mirrored = QuadraticBezierCurve::new([
+            vec3(0.0, 0.0, 0.0),
+            vec3(4.0, 0.0, 0.0),
+            vec3(4.0, 0.0, -4.0),
+        ]);
+        assert!((mirrored.curvature(0.3) + quadratic.curvature(0.3)).abs() < 1e-6);
+        let clothoid = ClothoidSegment::new(Vec3::ZERO, 0.0, 0.0, 0.5, 2.0);
+        assert!((clothoid.curvature(0.5) - 0.25).abs() < 1e-5);
+        let t = quadratic.t_of_length(2.0);
+        assert!((quadratic.length_of(t) - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_position_at_length() {
+        let curve = Curve::from_curves(vec![
+            QuadraticBezierCurve::new([
+                vec3(0.0, 0.0, 0.0),
+                vec3(10.0, 0.0, 0.0),
+                vec3(10.0, 0.0, 10.0),
+            ]),
+            QuadraticBezierCurve::new([
+                vec3(10.0, 0.0, 10.0),
+                vec3(10.0, 0.0, 30.0),
+                vec3(0.0, 0.0, 20.0),
+            ]),
+        ]);
+        for tolerance in [Curve::DEFAULT_TOLERANCE, 1e-4] {
+            let curve = curve.clone().with_tolerance(tolerance);
+            for i in 0..=100 {
+                let length = curve.length() * i as f32 / 100.0;
+                let (idx, prefix_len) = if length <= curve.sum_lengths[0] {
+                    (0, 0.0)
+                } else {
+                    (1, curve.sum_lengths[0])
+                };
+                // bisection is slow but exact up to f32
+                let (mut low, mut high) = (0.0_f32, 1.0_f32);
+                for _ in 0..40 {
+                    let mid = (low + high) / 2.0;
+                    if curve.curves[idx].length_of(mid) < length - prefix_len {
+                        low = mid;
+                    } else {
+                        high = mid;
+                    }
+                }
+                let exact = curve.curves[idx].position(low);
+                let err = (curve.position_at_length(length) - exact).length();
+                assert!(err < tolerance * 1.5, "{} at {}", err, length);
             }
         }
-        let t = low as f32 / 1024.0;
-        return self.curves[idx].position(t);
-        // binary search was code by GPT, here's the original code:
-        // iter over length_of curve
-        // for t in 0..1000 {
-        //     let t = t as f32 / 1000.0;
-        //     let len = self.curves[idx].length_of(t);
-        //     if len >= l - prefix_len {
-        //         return self.curves[idx].position(t);
-        //     }
-        // }
-        // let t = (l - prefix_len) / (self.sum_lengths[idx] - prefix_len);
-        // self.curves[idx].position(t)
+        assert_eq!(curve.position(0.0), curve.start());
+        assert_eq!(curve.position(1.0), curve.end());
+        assert_eq!(curve.position_at_length(-1.0), curve.start());
+        assert_eq!(curve.position_at_length(1e3), curve.end());
     }
-    pub fn velocity(&self, t: f32) -> Vec3 {
-        const EPS: f32 = 1e-6;
-        // TODO: improve the accuracy by calculate the actual
-        // derivative instead of numerical solution
-        if t < EPS {
-            return self.curves[0].position(1e-6) - self.curves[0].position(0.0);
+
+    #[test]
+    fn test_offset() {
+        let curve = Curve::from_segments(vec![
+            LineSegment::new([vec3(-10.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0)]).into(),
+            QuadraticBezierCurve::new([
+                vec3(0.0, 0.0, 0.0),
+                vec3(10.0, 0.0, 0.0),
+                vec3(10.0, 0.0, 10.0),
+            ])
+            .into(),
+            ClothoidSegment::new(vec3(10.0, 0.0, 10.0), FRAC_PI_2, 0.0, -0.1, 10.0).into(),
+        ]);
+        let check = |offset: &Curve, width: &dyn Fn(f32) -> f32, tolerance: f32| {
+            for i in 0..=200 {
+                let pt = offset.position(i as f32 / 200.0);
+                let projection = curve.project(pt - Vec3::Y * 0.5);
+                let expected = width(projection.length);
+                assert!(
+                    (projection.distance - expected).abs() < tolerance + 1e-4,
+                    "{} != {} at {}",
+                    projection.distance,
+                    expected,
+                    projection.length
+                );
+            }
+        };
+
+        let offset = curve.offset(2.0, 0.5);
+        check(&offset, &|_| 2.0, Curve::DEFAULT_TOLERANCE);
+        // positive is the right hand side, the inside of the first turn
+        assert!((offset.start() - vec3(-10.0, 0.5, 2.0)).length() < 1e-5);
+
+        let length = curve.length();
+        let profile = WidthProfile::new(vec![(5.0, 1.0), (15.0, 3.0), (length - 5.0, 1.0)]);
+        for tolerance in [1e-2, 1e-4] {
+            let offset = curve.offset_with(&profile, 0.5, tolerance);
+            check(&offset, &|l| profile.at(l), tolerance);
+        }
+        assert_eq!(profile.at(0.0), 1.0);
+        assert_eq!(profile.at(10.0), 2.0);
+        assert_eq!(profile.slope(10.0), 0.2);
+    }
+
+    #[test]
+    fn test_serde() {
+        let curve = Curve::from_segments(vec![
+            LineSegment::new([vec3(-4.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0)]).into(),
+            ArcSegment::from_tangent(Vec3::ZERO, Vec3::X, vec3(4.0, 0.0, 4.0))
+                .unwrap()
+                .into(),
+            ClothoidSegment::new(vec3(4.0, 0.0, 4.0), FRAC_PI_2, 0.0, 0.1, 5.0).into(),
+        ])
+        .with_tolerance(1e-4);
+        let text = ron::to_string(&curve).unwrap();
+        let loaded: Curve = ron::from_str(&text).unwrap();
+        assert_eq!(loaded.tolerance(), 1e-4);
+        assert_eq!(loaded.length(), curve.length());
+        assert_eq!(loaded.position(0.7), curve.position(0.7));
+        assert_eq!(ron::to_string(&loaded).unwrap(), text);
+    }
+
+    #[test]
+    fn test_export() {
+        let curve = Curve::from_segments(vec![
+            LineSegment::new([vec3(0.0, 0.0, 0.0), vec3(2.0, 0.0, 0.0)]).into(),
+            QuadraticBezierCurve::new([
+                vec3(2.0, 0.0, 0.0),
+                vec3(4.0, 0.0, 0.0),
+                vec3(4.0, 0.0, 2.0),
+            ])
+            .into(),
+        ]);
+        assert_eq!(curve.to_svg_path(), "M 0 0 L 2 0 Q 4 0 4 2");
+
+        for tolerance in [1e-1, 1e-3] {
+            let polyline = curve.to_polyline(tolerance);
+            assert_eq!(polyline[0], curve.start());
+            assert_eq!(*polyline.last().unwrap(), curve.end());
+            // every point of the curve is close to some edge of the polyline
+            for i in 0..=100 {
+                let pt = curve.position(i as f32 / 100.0);
+                let dist = polyline
+                    .windows(2)
+                    .map(|w| LineSegment::new([w[0], w[1]]).distance_to(pt))
+                    .fold(f32::MAX, f32::min);
+                assert!(dist <= tolerance + 1e-5, "{} > {}", dist, tolerance);
+            }
         }
-        if t > 1.0 - 1e-6 {
-            return self.curves.last().unwrap().position(1.0)
-                - self.curves.last().unwrap().position(1.0 - EPS);
+        assert!(curve.to_polyline(1e-1).len() < curve.to_polyline(1e-3).len());
+        // the straight piece needs only its ends, the stations match the points
+        let flat = curve.flatten(1e-3);
+        assert_eq!(flat[1], (2.0, vec3(2.0, 0.0, 0.0)));
+        assert_eq!(flat.last().unwrap().0, curve.length());
+        for &(length, pt) in flat.iter() {
+            assert!((curve.position_at_length(length) - pt).length() < 1e-3);
         }
-        return self.position(t + EPS) - self.position(t - EPS);
+        let segment = &curve.curves[1];
+        assert_eq!(segment.iter_positions(3).count(), 4);
+        assert_eq!(segment.iter_positions(3).last(), Some(segment.end()));
+
+        let svg = export::svg_document(&[curve], 0.1);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("d=\"M 0 0 L 2 0 Q 4 0 4 2\""));
     }
 
-    pub fn offset(&self, right: f32, top: f32) -> Self {
-        let mut rets = Vec::new();
-
-        for curve in &self.curves {
-            let curves = curve
-                .to_curve()
-                .curves
-                .iter()
-                .flat_map(|c| c.to_curve2(2., 1e2).curves)
+    #[test]
+    fn test_bbox() {
+        let segments: Vec<CurveSegment> = vec![
+            LineSegment::new([vec3(1.0, 2.0, 3.0), vec3(-1.0, 0.0, 5.0)]).into(),
+            QuadraticBezierCurve::new([
+                vec3(0.0, 0.0, 0.0),
+                vec3(4.0, 3.0, -2.0),
+                vec3(4.0, 0.0, 4.0),
+            ])
+            .into(),
+            CubicBezierCurve::new([
+                vec3(0.0, 0.0, 0.0),
+                vec3(5.0, 1.0, 5.0),
+                vec3(-3.0, -1.0, 2.0),
+                vec3(2.0, 0.0, 0.0),
+            ])
+            .into(),
+            ArcSegment::new(vec3(1.0, 0.0, 1.0), vec3(2.0, 0.0, 0.0), Vec3::Y, 4.0).into(),
+            ArcSegment::new(vec3(1.0, 0.0, 1.0), vec3(0.0, 0.0, 2.0), vec3(1.0, 1.0, 0.0), -2.5)
+                .into(),
+            ClothoidSegment::new(Vec3::ZERO, 0.3, 0.1, 0.8, 12.0).into(),
+        ];
+        for segment in segments.iter() {
+            let aabb = segment.bbox();
+            let samples = (0..=2000)
+                .map(|i| segment.position(i as f32 / 2000.0))
                 .collect::<Vec<_>>();
-            for curve in curves {
-                let [p0, p1, p2] = curve.ctrl_pts;
-                let v0 = p1 - p0;
-                let v1 = p2 - p1;
-                // calc normal
-                let n0 = vec3(-v0.z, 0.0, v0.x).normalize();
-                let n1 = vec3(-v1.z, 0.0, v1.x).normalize();
-                let q0 = p0 + n0 * right;
-                let q2 = p2 + n1 * right;
-                // p1 is the intersection of the two normals
-                // calculate the intersection of two lines
-                // p0 + t * v0 = p2 + s * v1
-                // t * v0 - s * v1 = p2 - p0
-                let det = v0.x * v1.z - v0.z * v1.x;
-                let q1 = if det.abs() < 1e-6 {
-                    // parallel
-                    println!("parallel");
-                    (q2 + q0) / 2.0
-                } else {
-                    let t = (v1.z * (q2.x - q0.x) - v1.x * (q2.z - q0.z)) / det;
-                    q0 + t * v0
-                };
+            let sampled = Aabb::from_points(samples.iter().copied());
+            // contains every point, and not much more
+            assert!(aabb.expand(1e-4).contains(sampled.min), "{:?}", segment);
+            assert!(aabb.expand(1e-4).contains(sampled.max), "{:?}", segment);
+            assert!(sampled.expand(1e-3).contains(aabb.min), "{:?}", segment);
+            assert!(sampled.expand(1e-3).contains(aabb.max), "{:?}", segment);
+        }
+        let curve = Curve::from_segments(segments);
+        assert!(!curve.bbox().contains(vec3(0.0, 10.0, 0.0)));
+        assert!(curve.bbox().contains(curve.position(0.5)));
+    }
+
+    #[test]
+    fn test_intersections() {
+        // an S curve crossing a straight line three times
+        let s_curve = CubicBezierCurve::new([
+            vec3(0.0, 0.0, -5.0),
+            vec3(5.0, 0.0, 20.0),
+            vec3(5.0, 0.0, -20.0),
+            vec3(10.0, 0.0, 5.0),
+        ])
+        .to_curve();
+        let line = LineSegment::new([vec3(-1.0, 0.0, 0.0), vec3(11.0, 0.0, 0.0)]).to_curve();
+        let hits = s_curve.intersections(&line);
+        assert_eq!(hits.len(), 3, "hits: {:?}", hits);
+        for (ta, tb, pt) in hits.iter() {
+            assert!(pt.z.abs() < 1e-3, "pt: {:?}", pt);
+            assert!((s_curve.project(*pt).length - ta * s_curve.length()).abs() < 1e-2);
+            assert!((line.project(*pt).length - tb * line.length()).abs() < 1e-2);
+        }
+        assert!(hits.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(line.intersections(&s_curve).len(), 3);
+
+        // an arc just touching the line
+        let arc = ArcSegment::new(vec3(5.0, 0.0, 2.0), vec3(-2.0, 0.0, 0.0), Vec3::Y, -PI);
+        let hits = arc.to_curve().intersections(&line);
+        assert_eq!(hits.len(), 1, "hits: {:?}", hits);
+        assert!((hits[0].2 - vec3(5.0, 0.0, 0.0)).length() < 1e-2);
+
+        // parallel lines never meet
+        let other = LineSegment::new([vec3(-1.0, 0.0, 1.0), vec3(11.0, 0.0, 1.0)]).to_curve();
+        assert!(line.intersections(&other).is_empty());
+    }
+
+    #[test]
+    fn test_project() {
+        let curve = Curve::from_segments(vec![
+            LineSegment::new([vec3(0.0, 0.0, 0.0), vec3(4.0, 0.0, 0.0)]).into(),
+            QuadraticBezierCurve::new([
+                vec3(4.0, 0.0, 0.0),
+                vec3(8.0, 0.0, 0.0),
+                vec3(8.0, 0.0, 4.0),
+            ])
+            .into(),
+        ]);
+        let projection = curve.project(vec3(2.0, 0.0, -1.0));
+        assert_eq!(projection.index, 0);
+        assert!((projection.t - 0.5).abs() < 1e-6);
+        assert!((projection.length - 2.0).abs() < 1e-6);
+        assert!((projection.distance - 1.0).abs() < 1e-6);
+
+        // the quadratic passes (7, 0, 1) at t = 0.5
+        let normal = vec3(1.0, 0.0, -1.0).normalize();
+        let projection = curve.project(vec3(7.0, 0.0, 1.0) + normal * 0.5);
+        assert_eq!(projection.index, 1);
+        assert!((projection.t - 0.5).abs() < 1e-5, "{:?}", projection);
+        assert!((projection.distance - 0.5).abs() < 1e-5, "{:?}", projection);
+        assert!((projection.length - 4.0 - curve.curves[1].length_of(0.5)).abs() < 1e-4);
+
+        let (a, b) = curve.split_at(vec3(7.0, 0.0, 1.0));
+        assert!((a.end() - vec3(7.0, 0.0, 1.0)).length() < 1e-5);
+        assert!((a.length() + b.length() - curve.length()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_clothoid_alignment() {
+        let (p, v) = (vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0));
+        let (q, u) = (vec3(30.0, 0.0, 20.0), vec3(0.0, 0.0, 1.0));
+        let curve = Curve::from_clothoid_alignment(p, v, q, u, 0.5).unwrap();
+        assert!((curve.start() - p).length() < 1e-4);
+        assert!((curve.end() - q).length() < 1e-4);
+        // tangent continuous at every joint
+        for pair in curve.curves.windows(2) {
+            let a = pair[0].tangent(1.0).normalize();
+            let b = pair[1].tangent(0.0).normalize();
+            assert!(a.dot(b) > 1. - 1e-3, "a: {:?}, b: {:?}", a, b);
+            assert!((pair[0].end() - pair[1].start()).length() < 1e-3);
+        }
+        let end_dir = curve.curves.last().unwrap().tangent(1.0).normalize();
+        assert!(end_dir.dot(u) > 1. - 1e-3, "end direction: {:?}", end_dir);
+
+        assert!(Curve::from_clothoid_alignment(p, v, q, -u, 0.5).is_err());
+        let straight = Curve::from_clothoid_alignment(p, v, vec3(5.0, 0.0, 0.0), v, 0.5);
+        assert!((straight.unwrap().length() - 5.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_with_profile() {
+        use profile::Pvi;
+
+        let plan = Curve::from_segments(vec![
+            LineSegment::new([vec3(0.0, 0.0, 0.0), vec3(60.0, 0.0, 0.0)]).into(),
+            QuadraticBezierCurve::new([
+                vec3(60.0, 0.0, 0.0),
+                vec3(100.0, 0.0, 0.0),
+                vec3(100.0, 0.0, 40.0),
+            ])
+            .into(),
+        ]);
+        let length = plan.length();
+        let profile = VerticalProfile::new(
+            vec![
+                Pvi::new(0.0, 0.0, 0.0),
+                Pvi::new(50.0, 4.0, 30.0),
+                Pvi::new(length, 0.0, 0.0),
+            ],
+            0.1,
+        )
+        .unwrap();
+        let road = plan.with_profile(&profile);
+        assert!(road.length() > length);
+        assert!((road.start() - Vec3::ZERO).length() < 1e-5);
+        assert!((road.end() - plan.end()).length() < 1e-4);
+        assert!((road.grade(0.0) - 0.08).abs() < 1e-4);
+        for i in 0..=100 {
+            let pt = road.position(i as f32 / 100.0);
+            let station = plan.project(vec3(pt.x, 0.0, pt.z)).length;
+            assert!(
+                (pt.y - profile.elevation(station)).abs() < Curve::DEFAULT_TOLERANCE + 1e-4,
+                "{} != {} at {}",
+                pt.y,
+                profile.elevation(station),
+                station
+            );
+        }
+        // the line under the crest became a parabola
+        assert!(matches!(road.curves[1], CurveSegment::Quadratic(_)));
+    }
+
+    #[test]
+    fn test_fillet() {
+        // a road along +x meeting one along +z at (10, 0, 0)
+        let a = LineSegment::new([vec3(0.0, 0.0, 0.0), vec3(15.0, 0.0, 0.0)]).to_curve();
+        let b = LineSegment::new([vec3(10.0, 0.0, -5.0), vec3(10.0, 0.0, 10.0)]).to_curve();
+        let fillet = Curve::fillet(&a, &b, 4.0).unwrap();
+        assert!((fillet.before.as_ref().unwrap().end() - vec3(6.0, 0.0, 0.0)).length() < 1e-3);
+        assert!((fillet.after.as_ref().unwrap().start() - vec3(10.0, 0.0, 4.0)).length() < 1e-3);
+        assert!((fillet.arc.curvature(0.5) - 0.25).abs() < 1e-3);
+        let joined = fillet.to_curve();
+        assert!((joined.length() - (6.0 + 6.0 + 4.0 * FRAC_PI_2)).abs() < 1e-3);
+        assert!(Curve::fillet(&a, &b, 20.0).is_err());
+
+        // turning left onto the same road
+        let c = LineSegment::new([vec3(10.0, 0.0, 10.0), vec3(10.0, 0.0, -10.0)]).to_curve();
+        let fillet = Curve::fillet(&a, &c, 2.0).unwrap();
+        assert!((fillet.arc.curvature(0.5) + 0.5).abs() < 1e-3);
+    }
 
-                rets.push(QuadraticBezierCurve::new([q0, q1 + top, q2]));
+    #[test]
+    fn test_tangent_join() {
+        let check = |curve: &Curve, p: Vec3, v: Vec3, q: Vec3, u: Vec3, min_radius: f32| {
+            assert!((curve.start() - p).length() < 1e-3);
+            assert!((curve.end() - q).length() < 1e-3);
+            assert!((curve.velocity(0.0) - v).length() < 1e-3);
+            assert!((curve.velocity(1.0) - u).length() < 1e-3);
+            for i in 0..=100 {
+                assert!(curve.curvature(i as f32 / 100.0).abs() <= 1.0 / min_radius + 1e-3);
             }
-            println!("{:?}", rets);
+        };
+        // a right turn
+        let (p, v, q, u) = (Vec3::ZERO, Vec3::X, vec3(10.0, 0.0, 10.0), Vec3::Z);
+        let curve = Curve::from_tangents(p, v, q, u, 5.0).unwrap();
+        check(&curve, p, v, q, u, 5.0);
+        // clothoids can't keep a radius of 9.5 here but a single arc can
+        let curve = Curve::from_tangents(p, v, q, u, 9.5).unwrap();
+        check(&curve, p, v, q, u, 9.5);
+        assert!(Curve::from_tangents(p, v, q, u, 11.0).is_err());
+
+        // a lane change, the tangents never meet
+        let lanes = (
+            LineSegment::new([vec3(-10.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0)]).to_curve(),
+            LineSegment::new([vec3(20.0, 0.0, 3.0), vec3(30.0, 0.0, 3.0)]).to_curve(),
+        );
+        let curve = Curve::tangent_join(&lanes.0, &lanes.1, 10.0).unwrap();
+        check(&curve, Vec3::ZERO, v, vec3(20.0, 0.0, 3.0), v, 10.0);
+        assert!(Curve::tangent_join(&lanes.0, &lanes.1, 100.0).is_err());
+    }
+
+    #[test]
+    fn test_editing() {
+        let curve = Curve::from_segments(vec![
+            LineSegment::new([vec3(-4.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0)]).into(),
+            ArcSegment::new(
+                vec3(0.0, 0.0, 4.0),
+                vec3(0.0, 0.0, -4.0),
+                -Vec3::Y,
+                FRAC_PI_2,
+            )
+            .into(),
+            ClothoidSegment::new(vec3(4.0, 0.0, 4.0), FRAC_PI_2, 0.25, 0.0, 6.0).into(),
+            QuadraticBezierCurve::new([
+                vec3(3.0, 0.0, 9.9),
+                vec3(2.0, 0.0, 14.0),
+                vec3(6.0, 0.0, 16.0),
+            ])
+            .into(),
+        ]);
+        let length = curve.length();
+
+        let reversed = curve.reverse();
+        assert!((reversed.length() - length).abs() < 1e-4);
+        for i in 0..=20 {
+            let t = i as f32 / 20.0;
+            assert!((reversed.position(t) - curve.position(1.0 - t)).length() < 2e-3);
+            assert!((reversed.velocity(t) + curve.velocity(1.0 - t)).length() < 1e-2);
         }
-        let sum_lengths = rets
-            .iter()
-            .map(|curve| curve.length())
-            .scan(0.0, |sum, x| {
-                *sum += x;
-                Some(*sum)
-            })
-            .collect();
-        Self {
-            curves: rets,
-            sum_lengths,
+        // the right turn becomes a left turn
+        assert!((reversed.curvature(1.0 - 6.0 / length) + 0.25).abs() < 1e-3);
+
+        // trims are exact, no round trip through a projected point
+        let trimmed = curve.trim_start(5.0).unwrap().trim_end(3.0).unwrap();
+        assert!((trimmed.length() - (length - 8.0)).abs() < 1e-4);
+        assert!((trimmed.start() - curve.position_at_length(5.0)).length() < 2e-3);
+        assert!((trimmed.end() - curve.position_at_length(length - 3.0)).length() < 2e-3);
+        assert!(curve.trim_start(length + 1.0).is_err());
+        let (head, tail) = curve.split_at_length(4.0);
+        assert_eq!(head.curves.len(), 1);
+        assert!((tail.length() - (length - 4.0)).abs() < 1e-4);
+
+        let joined = head.concat(&tail, Continuity::G1).unwrap();
+        assert!((joined.length() - length).abs() < 1e-4);
+        let kinked = LineSegment::new([Vec3::ZERO, vec3(0.0, 0.0, -3.0)]).to_curve();
+        assert!(head.concat(&kinked, Continuity::G0).is_ok());
+        assert!(head.concat(&kinked, Continuity::G1).is_err());
+        assert!(kinked.concat(&head, Continuity::G0).is_err());
+
+        let points = curve.resample(1.0);
+        assert_eq!(points.len(), length.ceil() as usize + 1);
+        let step = length / (points.len() - 1) as f32;
+        for pair in points.windows(2) {
+            let l0 = curve.project(pair[0]).length;
+            let l1 = curve.project(pair[1]).length;
+            assert!((l1 - l0 - step).abs() < 1e-2);
         }
     }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Curve::try_from_segments(vec![]).unwrap_err(),
+            GeometryError::EmptyCurve
+        );
+        let nan = vec3(f32::NAN, 0.0, 0.0);
+        assert_eq!(
+            LineSegment::try_new([Vec3::ZERO, nan]).unwrap_err(),
+            GeometryError::NaN { what: "line" }
+        );
+        assert!(matches!(
+            QuadraticBezierCurve::try_new([Vec3::X; 3]),
+            Err(GeometryError::Degenerate { .. })
+        ));
+        assert!(ClothoidSegment::try_new(Vec3::ZERO, 0.0, 0.0, 0.1, 0.0).is_err());
+        assert!(Curve::form_two_velocity(Vec3::ZERO, nan, Vec3::X, Vec3::X).is_err());
+
+        let curve = LineSegment::new([Vec3::ZERO, vec3(10.0, 0.0, 0.0)]).to_curve();
+        assert_eq!(
+            curve.slice_by_length(2.0, 12.0).unwrap_err(),
+            GeometryError::InvalidRange {
+                start: 2.0,
+                end: 12.0,
+                limit: 10.0
+            }
+        );
+        let away = LineSegment::new([vec3(11.0, 0.0, 0.0), vec3(12.0, 0.0, 0.0)]).to_curve();
+        assert!(matches!(
+            curve.concat(&away, Continuity::G0),
+            Err(GeometryError::Discontinuous { .. })
+        ));
+        // a U-turn in 2 meters can't keep a radius of 5
+        let tight = Curve::from_tangents(Vec3::ZERO, Vec3::X, vec3(0.0, 0.0, 2.0), -Vec3::X, 5.0);
+        assert!(matches!(tight, Err(GeometryError::TooTight { .. })));
+
+        let loaded: Result<Curve, _> = ron::from_str("(curves: [], tolerance: 0.01)");
+        assert!(loaded.is_err());
+    }
 }
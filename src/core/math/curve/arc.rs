@@ -0,0 +1,251 @@
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use bevy::math::{Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::core::math::{
+    aabb::Aabb,
+    error::{check_finite, GeometryError, Result},
+};
+
+use super::{cubic::CubicBezierCurve, segment::Segment, Curve, CurveSegment};
+
+/// a circular arc. it starts at `center + from` and rotates around `normal` by `sweep`
+/// radians (counter-clockwise when looking against `normal`).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArcSegment {
+    pub center: Vec3,
+    /// vector from the center to the start point
+    pub from: Vec3,
+    /// unit rotation axis
+    pub normal: Vec3,
+    pub sweep: f32,
+}
+
+impl ArcSegment {
+    pub fn new(center: Vec3, from: Vec3, normal: Vec3, sweep: f32) -> Self {
+        Self {
+            center,
+            from,
+            normal: normal.normalize(),
+            sweep,
+        }
+    }
+
+    /// fails on non-finite input, a zero radius or a zero |normal|
+    pub fn try_new(center: Vec3, from: Vec3, normal: Vec3, sweep: f32) -> Result<Self> {
+        check_finite("arc", &[center, from, normal, Vec3::splat(sweep)])?;
+        if from == Vec3::ZERO || normal == Vec3::ZERO {
+            return Err(GeometryError::Degenerate {
+                what: "arc",
+                points: vec![center, from, normal],
+            });
+        }
+        Ok(Self::new(center, from, normal, sweep))
+    }
+
+    /// the arc that starts at |start| heading to |direction| and passes through |end|.
+    /// return None if |end| is on the line of |direction|
+    pub fn from_tangent(start: Vec3, direction: Vec3, end: Vec3) -> Option<Self> {
+        let d = end - start;
+        let axis = direction.cross(d);
+        if axis.length() < 1e-6 {
+            return None;
+        }
+        let normal = axis.normalize();
+        // unit vector from start to the center
+        let inward = normal.cross(direction).normalize();
+        let radius = d.length_squared() / (2.0 * d.dot(inward));
+        let center = start + inward * radius;
+        let from = start - center;
+        let to = end - center;
+        let mut sweep = normal.dot(from.cross(to)).atan2(from.dot(to));
+        if sweep < 0.0 {
+            sweep += TAU;
+        }
+        Some(Self::new(center, from, normal, sweep))
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.from.length()
+    }
+
+    pub fn to_curve(&self) -> Curve {
+        Curve::from_segments(vec![CurveSegment::Arc(self.clone())])
+    }
+
+    /// whether the arc lies on a plane parallel to the ground
+    pub fn is_horizontal(&self) -> bool {
+        self.normal.dot(Vec3::Y).abs() > 1. - 1e-4
+    }
+
+    /// approximate the arc with cubic Bézier curves, each spanning at most 90 degrees
+    pub fn to_cubics(&self) -> Vec<CubicBezierCurve> {
+        let n = (self.sweep.abs() / FRAC_PI_2).ceil().max(1.) as usize;
+        let step = self.sweep / n as f32;
+        // the handle length of a cubic approximating a unit arc
+        let k = 4.0 / 3.0 * (step / 4.0).tan();
+        (0..n)
+            .map(|i| {
+                let a = Quat::from_axis_angle(self.normal, step * i as f32) * self.from;
+                let b = Quat::from_axis_angle(self.normal, step * (i + 1) as f32) * self.from;
+                let ta = self.normal.cross(a);
+                let tb = self.normal.cross(b);
+                CubicBezierCurve::new([
+                    self.center + a,
+                    self.center + a + ta * k,
+                    self.center + b - tb * k,
+                    self.center + b,
+                ])
+            })
+            .collect()
+    }
+
+    /// shift a horizontal arc by |right| on the ground plane and |top| upwards. the result is
+    /// the exact parallel arc.
+    pub fn offset(&self, right: f32, top: f32) -> Self {
+        let tangent = self.tangent(0.0);
+        // the right hand side of the heading direction, same as the other segments
+        let side = Vec3::new(-tangent.z, 0.0, tangent.x).normalize_or_zero();
+        let outward = self.from.normalize_or_zero();
+        let radius = self.radius() + right * side.dot(outward).signum();
+        Self {
+            center: self.center + Vec3::Y * top,
+            from: outward * radius,
+            normal: self.normal,
+            sweep: self.sweep,
+        }
+    }
+}
+
+impl Segment for ArcSegment {
+    fn position(&self, t: f32) -> Vec3 {
+        self.center + Quat::from_axis_angle(self.normal, self.sweep * t) * self.from
+    }
+
+    fn tangent(&self, t: f32) -> Vec3 {
+        self.sweep
+            * self
+                .normal
+                .cross(Quat::from_axis_angle(self.normal, self.sweep * t) * self.from)
+    }
+
+    fn length_of(&self, t: f32) -> f32 {
+        self.radius() * self.sweep.abs() * t
+    }
+
+    fn acceleration(&self, t: f32) -> Vec3 {
+        -self.sweep * self.sweep * (Quat::from_axis_angle(self.normal, self.sweep * t) * self.from)
+    }
+
+    fn split_at_t(&self, t: f32) -> (Self, Self) {
+        let mid = Quat::from_axis_angle(self.normal, self.sweep * t) * self.from;
+        (
+            Self {
+                sweep: self.sweep * t,
+                ..self.clone()
+            },
+            Self {
+                from: mid,
+                sweep: self.sweep * (1.0 - t),
+                ..self.clone()
+            },
+        )
+    }
+
+    /// start from the end and turn back by the same sweep
+    fn reverse(&self) -> Self {
+        Self {
+            from: self.end() - self.center,
+            sweep: -self.sweep,
+            ..self.clone()
+        }
+    }
+
+    fn translate(&self, offset: Vec3) -> Self {
+        Self {
+            center: self.center + offset,
+            ..self.clone()
+        }
+    }
+
+    /// the end points plus the points of the full circle that are extreme on some axis and
+    /// fall within the sweep
+    fn bbox(&self) -> Aabb {
+        // position(t) - center = from cos(a) + (normal x from) sin(a), a = sweep * t
+        let side = self.normal.cross(self.from);
+        let extremes = (0..3).flat_map(|axis| {
+            let a = side[axis].atan2(self.from[axis]);
+            [a, a + PI].map(|a| {
+                // the same angle, reached by turning the way the sweep goes
+                let t = (a * self.sweep.signum()).rem_euclid(TAU) / self.sweep.abs();
+                (t < 1.0).then(|| self.position(t))
+            })
+        });
+        Aabb::from_points([self.start(), self.end()].into_iter().chain(extremes.flatten()))
+    }
+
+    fn closest_t(&self, pt: Vec3) -> f32 {
+        let d = pt - self.center;
+        let d = d - self.normal * d.dot(self.normal);
+        if d.length() < 1e-6 || self.sweep.abs() < 1e-6 {
+            return 0.0;
+        }
+        // angle from the start point, measured along the sweep direction
+        let angle =
+            self.normal.dot(self.from.cross(d)).atan2(self.from.dot(d)) * self.sweep.signum();
+        let angle = angle.rem_euclid(TAU);
+        if angle <= self.sweep.abs() {
+            return angle / self.sweep.abs();
+        }
+        if (self.start() - pt).length() < (self.end() - pt).length() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use bevy::math::vec3;
+
+    use super::*;
+
+    #[test]
+    fn test_arc_segment() {
+        // quarter circle from (1, 0, 0) to (0, 0, -1) around +Y
+        let arc = ArcSegment::from_tangent(
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 0.0, -1.0),
+            vec3(0.0, 0.0, -1.0),
+        )
+        .unwrap();
+        assert!(arc.center.length() < 1e-5, "center: {:?}", arc.center);
+        assert!((arc.length() - PI / 2.0).abs() < 1e-5);
+        assert!((arc.end() - vec3(0.0, 0.0, -1.0)).length() < 1e-5);
+        assert!(arc.tangent(0.0).normalize().dot(vec3(0.0, 0.0, -1.0)) > 1. - 1e-5);
+
+        let mid = vec3(1.0, 0.0, -1.0).normalize();
+        assert!((arc.closest_t(mid * 3.0) - 0.5).abs() < 1e-5);
+        assert!((arc.distance_to(mid * 3.0) - 2.0).abs() < 1e-5);
+        assert_eq!(arc.closest_t(vec3(1.0, 0.0, 0.1)), 0.0);
+
+        let (a, b) = arc.split_at_t(0.5);
+        assert!((a.end() - mid).length() < 1e-5);
+        assert!((b.start() - mid).length() < 1e-5);
+        assert!((a.length() + b.length() - arc.length()).abs() < 1e-5);
+
+        for cubic in arc.to_cubics() {
+            for pt in cubic.iter_positions(16) {
+                assert!((pt.length() - 1.0).abs() < 1e-3, "pt: {:?}", pt);
+            }
+        }
+
+        // heading -z, the offset side is +x, away from the center
+        let offset = arc.offset(0.5, 0.0);
+        assert!((offset.radius() - 1.5).abs() < 1e-5);
+    }
+}
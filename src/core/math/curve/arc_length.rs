@@ -0,0 +1,103 @@
+//! arc length reparameterisation of a chain of segments.
+//!
+//! the table samples every segment adaptively: an interval is split until linear
+//! interpolation of t between its ends lands within `tolerance` meters of the real point at
+//! the middle. a lookup is then a binary search plus one lerp, no `length_of` calls.
+
+use super::{segment::Segment, CurveSegment};
+
+/// deepest split of a single interval, 2^-12 of a segment
+const MAX_DEPTH: usize = 12;
+/// every segment gets at least this many intervals, so a symmetric segment can not fool the
+/// midpoint check at the top level
+const MIN_INTERVALS: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    /// arc length from the start of the curve
+    length: f32,
+    index: usize,
+    t: f32,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct ArcLengthTable {
+    samples: Vec<Sample>,
+}
+
+impl ArcLengthTable {
+    pub(super) fn new(curves: &[CurveSegment], tolerance: f32) -> Self {
+        let mut samples = vec![];
+        let mut prefix_len = 0.0;
+        for (index, curve) in curves.iter().enumerate() {
+            let sample = |t: f32| Sample {
+                length: prefix_len + curve.length_of(t),
+                index,
+                t,
+            };
+            let mut prev = sample(0.0);
+            samples.push(prev);
+            for i in 1..=MIN_INTERVALS {
+                let next = sample(i as f32 / MIN_INTERVALS as f32);
+                refine(
+                    &sample,
+                    curve,
+                    prev,
+                    next,
+                    tolerance,
+                    MAX_DEPTH,
+                    &mut samples,
+                );
+                samples.push(next);
+                prev = next;
+            }
+            prefix_len = prev.length;
+        }
+        Self { samples }
+    }
+
+    /// the segment index and the t on that segment at |length| from the start
+    pub(super) fn locate(&self, length: f32) -> (usize, f32) {
+        let i = self
+            .samples
+            .partition_point(|s| s.length < length)
+            .clamp(1, self.samples.len() - 1);
+        let (a, b) = (self.samples[i - 1], self.samples[i]);
+        if length <= a.length {
+            return (a.index, a.t);
+        }
+        if a.index != b.index || b.length - a.length < 1e-9 {
+            return (b.index, b.t);
+        }
+        let k = ((length - a.length) / (b.length - a.length)).min(1.0);
+        (a.index, a.t + (b.t - a.t) * k)
+    }
+}
+
+/// push the samples strictly between |a| and |b| that keep the interpolation error below
+/// |tolerance|
+fn refine(
+    sample: &impl Fn(f32) -> Sample,
+    curve: &CurveSegment,
+    a: Sample,
+    b: Sample,
+    tolerance: f32,
+    depth: usize,
+    samples: &mut Vec<Sample>,
+) {
+    if depth == 0 {
+        return;
+    }
+    let mid = sample((a.t + b.t) / 2.0);
+    let lerp_t = if b.length - a.length > 1e-9 {
+        a.t + (b.t - a.t) * (mid.length - a.length) / (b.length - a.length)
+    } else {
+        a.t
+    };
+    if (curve.position(lerp_t) - curve.position(mid.t)).length() <= tolerance {
+        return;
+    }
+    refine(sample, curve, a, mid, tolerance, depth - 1, samples);
+    samples.push(mid);
+    refine(sample, curve, mid, b, tolerance, depth - 1, samples);
+}
@@ -0,0 +1,249 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use bevy::math::{vec3, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::core::math::{
+    aabb::Aabb,
+    error::{check_finite, GeometryError, Result},
+    poly::solve_quadratic,
+};
+
+use super::{cubic::CubicBezierCurve, segment::Segment, Curve, CurveSegment};
+
+/// an Euler spiral on the ground plane. the curvature changes linearly from `k0` to `k1`
+/// along `length`.
+///
+/// heading is the angle of the direction `(cos, 0, sin)`, so positive curvature turns from
+/// +x towards +z.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClothoidSegment {
+    pub origin: Vec3,
+    pub heading: f32,
+    pub k0: f32,
+    pub k1: f32,
+    pub length: f32,
+}
+
+impl ClothoidSegment {
+    pub fn new(origin: Vec3, heading: f32, k0: f32, k1: f32, length: f32) -> Self {
+        Self {
+            origin,
+            heading,
+            k0,
+            k1,
+            length,
+        }
+    }
+
+    /// fails on non-finite input or a length that isn't positive
+    pub fn try_new(origin: Vec3, heading: f32, k0: f32, k1: f32, length: f32) -> Result<Self> {
+        check_finite(
+            "clothoid",
+            &[origin, vec3(heading, k0, k1), Vec3::splat(length)],
+        )?;
+        if length <= 0.0 {
+            return Err(GeometryError::InvalidArgument {
+                name: "clothoid length",
+                value: length,
+            });
+        }
+        Ok(Self::new(origin, heading, k0, k1, length))
+    }
+
+    pub fn to_curve(&self) -> Curve {
+        Curve::from_segments(vec![CurveSegment::Clothoid(self.clone())])
+    }
+
+    /// curvature at arc length s
+    pub fn curvature_at(&self, s: f32) -> f32 {
+        if self.length <= 0.0 {
+            return self.k0;
+        }
+        self.k0 + (self.k1 - self.k0) * s / self.length
+    }
+
+    /// heading at arc length s
+    pub fn heading_at(&self, s: f32) -> f32 {
+        let dk = if self.length <= 0.0 {
+            0.0
+        } else {
+            (self.k1 - self.k0) / self.length
+        };
+        self.heading + self.k0 * s + 0.5 * dk * s * s
+    }
+
+    /// approximate the spiral with Hermite cubics, each turning by at most ~0.25 rad
+    pub fn to_cubics(&self) -> Vec<CubicBezierCurve> {
+        let turn = (self.heading_at(self.length) - self.heading).abs()
+            + self.k0.abs().max(self.k1.abs()) * self.length;
+        let n = ((turn / 0.25).ceil() as usize).clamp(1, 256);
+        (0..n)
+            .map(|i| {
+                let (t0, t1) = (i as f32 / n as f32, (i + 1) as f32 / n as f32);
+                let handle = self.length * (t1 - t0) / 3.0;
+                let (p0, p3) = (self.position(t0), self.position(t1));
+                CubicBezierCurve::new([
+                    p0,
+                    p0 + self.direction_at(t0 * self.length) * handle,
+                    p3 - self.direction_at(t1 * self.length) * handle,
+                    p3,
+                ])
+            })
+            .collect()
+    }
+
+    fn direction_at(&self, s: f32) -> Vec3 {
+        let (sin, cos) = self.heading_at(s).sin_cos();
+        vec3(cos, 0.0, sin)
+    }
+
+    /// integrate the direction from 0 to s. there is no closed form (it needs the Fresnel
+    /// integrals), so the integral is split into pieces turning less than ~0.25 rad and each
+    /// piece uses 5-point Gauss-Legendre quadrature.
+    fn integrate(&self, s: f32) -> Vec3 {
+        const NODES: [(f32, f32); 5] = [
+            (0.0, 0.568_888_9),
+            (-0.538_469_3, 0.478_628_7),
+            (0.538_469_3, 0.478_628_7),
+            (-0.906_179_8, 0.236_926_9),
+            (0.906_179_8, 0.236_926_9),
+        ];
+        if s <= 0.0 {
+            return Vec3::ZERO;
+        }
+        let turn = self.k0.abs().max(self.k1.abs()) * s;
+        let n = ((turn / 0.25).ceil() as usize).clamp(1, 256);
+        let h = s / n as f32;
+        (0..n)
+            .map(|i| {
+                let mid = (i as f32 + 0.5) * h;
+                NODES
+                    .iter()
+                    .map(|(x, w)| *w * self.direction_at(mid + x * h / 2.0))
+                    .sum::<Vec3>()
+                    * (h / 2.0)
+            })
+            .sum()
+    }
+}
+
+impl Segment for ClothoidSegment {
+    fn position(&self, t: f32) -> Vec3 {
+        self.origin + self.integrate(t * self.length)
+    }
+
+    fn tangent(&self, t: f32) -> Vec3 {
+        self.direction_at(t * self.length) * self.length
+    }
+
+    fn acceleration(&self, t: f32) -> Vec3 {
+        let s = t * self.length;
+        let dir = self.direction_at(s);
+        // d(direction)/ds = curvature * (direction rotated towards +z)
+        vec3(-dir.z, 0.0, dir.x) * self.curvature_at(s) * self.length * self.length
+    }
+
+    fn length_of(&self, t: f32) -> f32 {
+        self.length * t
+    }
+
+    fn split_at_t(&self, t: f32) -> (Self, Self) {
+        let s = self.length * t;
+        let k = self.curvature_at(s);
+        (
+            Self::new(self.origin, self.heading, self.k0, k, s),
+            Self::new(
+                self.position(t),
+                self.heading_at(s),
+                k,
+                self.k1,
+                self.length - s,
+            ),
+        )
+    }
+
+    /// driving backwards turns the other way, so the curvature flips sign too
+    fn reverse(&self) -> Self {
+        Self::new(
+            self.end(),
+            self.heading_at(self.length) + PI,
+            -self.k1,
+            -self.k0,
+            self.length,
+        )
+    }
+
+    fn translate(&self, offset: Vec3) -> Self {
+        Self {
+            origin: self.origin + offset,
+            ..self.clone()
+        }
+    }
+
+    /// x is extreme where the heading is an odd multiple of pi/2, z where it is a multiple
+    /// of pi. the heading is
+    /// quadratic in the arc length, so both come from [`solve_quadratic`].
+    fn bbox(&self) -> Aabb {
+        let dk = if self.length <= 0.0 {
+            0.0
+        } else {
+            (self.k1 - self.k0) / self.length
+        };
+        let mut headings = vec![self.heading_at(0.0), self.heading_at(self.length)];
+        if dk != 0.0 && (0.0..self.length).contains(&(-self.k0 / dk)) {
+            headings.push(self.heading_at(-self.k0 / dk));
+        }
+        let min = headings.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = headings.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let first = (min / FRAC_PI_2).ceil() as i64;
+        let last = (max / FRAC_PI_2).floor() as i64;
+        let extremes = (first..=last.min(first + 256)).flat_map(|m| {
+            // heading + k0 s + dk / 2 s^2 = m pi / 2
+            solve_quadratic(
+                0.5 * dk as f64,
+                self.k0 as f64,
+                self.heading as f64 - m as f64 * std::f64::consts::FRAC_PI_2,
+            )
+            .into_iter()
+            .map(|s| s as f32)
+            .filter(|s| *s > 0.0 && *s < self.length)
+            .map(|s| self.position(s / self.length))
+            .collect::<Vec<_>>()
+        });
+        Aabb::from_points([self.start(), self.end()].into_iter().chain(extremes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use bevy::math::vec3;
+
+    use super::*;
+
+    #[test]
+    fn test_clothoid_segment() {
+        // constant curvature is a circle
+        let circle = ClothoidSegment::new(Vec3::ZERO, 0.0, 1.0, 1.0, PI);
+        assert!((circle.end() - vec3(0.0, 0.0, 2.0)).length() < 1e-4);
+
+        let spiral = ClothoidSegment::new(Vec3::ZERO, 0.0, 0.0, 0.5, 4.0);
+        // heading after the spiral is L * k1 / 2
+        let end_dir = spiral.tangent(1.0).normalize();
+        assert!((end_dir.z.atan2(end_dir.x) - 1.0).abs() < 1e-5);
+        let polyline = spiral
+            .to_curve()
+            .iter_positions(512)
+            .collect::<Vec<Vec3>>()
+            .windows(2)
+            .fold(0.0, |acc, p| acc + (p[0] - p[1]).length());
+        assert!((polyline - 4.0).abs() < 1e-3, "length: {:?}", polyline);
+
+        let (a, b) = spiral.split_at_t(0.25);
+        assert!((a.end() - b.start()).length() < 1e-5);
+        assert!((b.end() - spiral.end()).length() < 1e-4);
+        assert!((a.k1 - 0.125).abs() < 1e-6);
+    }
+}
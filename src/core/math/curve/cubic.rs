@@ -0,0 +1,356 @@
+use bevy::math::{vec3, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::core::math::{
+    aabb::Aabb,
+    error::{check_finite, GeometryError, Result},
+    line::{Intersection, Line, LineByPQ},
+    poly::solve_quadratic,
+};
+
+use super::{segment::Segment, Curve, CurveSegment};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CubicBezierCurve {
+    pub ctrl_pts: [Vec3; 4],
+}
+
+impl CubicBezierCurve {
+    pub fn new(ctrl_pts: [Vec3; 4]) -> Self {
+        Self { ctrl_pts }
+    }
+
+    /// fails on non-finite control points or when they all coincide
+    pub fn try_new(ctrl_pts: [Vec3; 4]) -> Result<Self> {
+        check_finite("cubic curve", &ctrl_pts)?;
+        if ctrl_pts.iter().all(|&p| p == ctrl_pts[0]) {
+            return Err(GeometryError::Degenerate {
+                what: "cubic curve",
+                points: ctrl_pts.to_vec(),
+            });
+        }
+        Ok(Self::new(ctrl_pts))
+    }
+
+    pub fn start(&self) -> Vec3 {
+        self.ctrl_pts[0]
+    }
+
+    pub fn end(&self) -> Vec3 {
+        self.ctrl_pts[3]
+    }
+
+    pub fn to_curve(&self) -> Curve {
+        Curve::from_segments(vec![CurveSegment::Cubic(self.clone())])
+    }
+
+    /// subdivide the curve until the control polygon of each piece turns by less than
+    /// |min_angle| (radians, measured as in [`QuadraticBezierCurve::to_curve2`]) and each
+    /// piece is shorter than |max_length|.
+    ///
+    /// [`QuadraticBezierCurve::to_curve2`]: super::quadratic::QuadraticBezierCurve::to_curve2
+    pub fn to_curve2(&self, min_angle: f32, max_length: f32) -> Curve {
+        Curve::from_segments(
+            self.subdivide(min_angle, max_length, 0)
+                .into_iter()
+                .map(CurveSegment::Cubic)
+                .collect(),
+        )
+    }
+
+    fn subdivide(&self, min_angle: f32, max_length: f32, depth: usize) -> Vec<Self> {
+        const MAX_DEPTH: usize = 16;
+        let [p0, p1, p2, p3] = self.ctrl_pts;
+        if depth >= MAX_DEPTH || (p3 - p0).length() < 1e-4 {
+            return vec![self.clone()];
+        }
+        // the angle between the incoming and outgoing tangents, pi means a straight line
+        let v = self.velocity(0.0);
+        let u = self.velocity(1.0);
+        let angle = if v.length() < 1e-4 || u.length() < 1e-4 {
+            (p1 - p0).angle_between(p2 - p3)
+        } else {
+            v.angle_between(-u)
+        };
+        // an S shape has parallel end tangents, so check the control polygon as well
+        let inflected = (p1 - p0).cross(p2 - p1).dot((p2 - p1).cross(p3 - p2)) < 0.0;
+        if angle > min_angle && !inflected && self.length() < max_length {
+            return vec![self.clone()];
+        }
+        let (a, b) = self.split_at_t(0.5);
+        let mut ret = a.subdivide(min_angle, max_length, depth + 1);
+        ret.append(&mut b.subdivide(min_angle, max_length, depth + 1));
+        ret
+    }
+
+    /// split the curve at the point closest to |pt|
+    pub fn split_at(&self, pt: Vec3) -> (Curve, Curve) {
+        let (curve1, curve2) = self.split_at_t(self.closest_t(pt));
+        (curve1.to_curve(), curve2.to_curve())
+    }
+
+    /// split the curve at t with de Casteljau's algorithm
+    pub fn split_at_t(&self, t: f32) -> (Self, Self) {
+        let [p0, p1, p2, p3] = self.ctrl_pts;
+        let p01 = p0.lerp(p1, t);
+        let p12 = p1.lerp(p2, t);
+        let p23 = p2.lerp(p3, t);
+        let p012 = p01.lerp(p12, t);
+        let p123 = p12.lerp(p23, t);
+        let p0123 = p012.lerp(p123, t);
+        (
+            Self::new([p0, p01, p012, p0123]),
+            Self::new([p0123, p123, p23, p3]),
+        )
+    }
+
+    pub fn iter_positions(&self, n: isize) -> impl Iterator<Item = Vec3> + '_ {
+        let n = n.max(1);
+        (0..=n).map(move |i| self.position(i as f32 / n as f32))
+    }
+
+    pub fn position(&self, t: f32) -> Vec3 {
+        // B(t) = (1-t)^3 * P0 + 3(1-t)^2 t * P1 + 3(1-t)t^2 * P2 + t^3 * P3
+        let [p0, p1, p2, p3] = self.ctrl_pts;
+        let s = 1.0 - t;
+        s * s * s * p0 + 3.0 * s * s * t * p1 + 3.0 * s * t * t * p2 + t * t * t * p3
+    }
+
+    /// the box around the end points and the extremes on each axis, the roots of the
+    /// derivative
+    pub fn bbox(&self) -> Aabb {
+        let [p0, p1, p2, p3] = self.ctrl_pts;
+        // B'(t) / 3 = a t^2 + b t + c
+        let a = (-p0 + 3.0 * p1 - 3.0 * p2 + p3).as_dvec3();
+        let b = (2.0 * (p0 - 2.0 * p1 + p2)).as_dvec3();
+        let c = (p1 - p0).as_dvec3();
+        let extremes = (0..3)
+            .flat_map(|axis| solve_quadratic(a[axis], b[axis], c[axis]))
+            .filter(|t| *t > 0.0 && *t < 1.0)
+            .map(|t| self.position(t as f32));
+        Aabb::from_points([p0, p3].into_iter().chain(extremes))
+    }
+
+    /// the derivative B'(t)
+    pub fn velocity(&self, t: f32) -> Vec3 {
+        let [p0, p1, p2, p3] = self.ctrl_pts;
+        let s = 1.0 - t;
+        3.0 * s * s * (p1 - p0) + 6.0 * s * t * (p2 - p1) + 3.0 * t * t * (p3 - p2)
+    }
+
+    pub fn length(&self) -> f32 {
+        self.length_of(1.)
+    }
+
+    /// arc length from 0 to t.
+    ///
+    /// cubic Bézier curves have no closed-form arc length, so |B'(t)| is integrated with
+    /// adaptive Gauss-Legendre quadrature.
+    pub fn length_of(&self, t: f32) -> f32 {
+        const TOL: f32 = 1e-5;
+        if t <= 0.0 {
+            return 0.0;
+        }
+        let whole = self.gauss_legendre(0.0, t);
+        self.adaptive_length(0.0, t, whole, TOL, 0)
+    }
+
+    fn adaptive_length(&self, a: f32, b: f32, whole: f32, tol: f32, depth: usize) -> f32 {
+        const MAX_DEPTH: usize = 12;
+        let mid = (a + b) / 2.0;
+        let left = self.gauss_legendre(a, mid);
+        let right = self.gauss_legendre(mid, b);
+        if depth >= MAX_DEPTH || (left + right - whole).abs() <= tol {
+            return left + right;
+        }
+        self.adaptive_length(a, mid, left, tol / 2.0, depth + 1)
+            + self.adaptive_length(mid, b, right, tol / 2.0, depth + 1)
+    }
+
+    /// 5-point Gauss-Legendre quadrature of |B'(t)| over [a, b]
+    fn gauss_legendre(&self, a: f32, b: f32) -> f32 {
+        const NODES: [(f32, f32); 5] = [
+            (0.0, 0.568_888_9),
+            (-0.538_469_3, 0.478_628_7),
+            (0.538_469_3, 0.478_628_7),
+            (-0.906_179_8, 0.236_926_9),
+            (0.906_179_8, 0.236_926_9),
+        ];
+        let half = (b - a) / 2.0;
+        let mid = (a + b) / 2.0;
+        NODES
+            .iter()
+            .map(|(x, w)| w * self.velocity(mid + half * x).length())
+            .sum::<f32>()
+            * half
+    }
+
+    /// approximate the curve shifted by |right| on the ground plane and |top| upwards.
+    ///
+    /// this uses the Tiller-Hanson construction: each leg of the control polygon is moved
+    /// along its normal and the new control points are the intersections of adjacent legs.
+    /// callers should subdivide with [`Self::to_curve2`] first to keep the error small.
+    pub fn offset(&self, right: f32, top: f32) -> Self {
+        let [p0, p1, p2, p3] = self.ctrl_pts;
+        // degenerate legs take the direction of the curve at that end
+        let leg = |a: Vec3, b: Vec3, fallback: Vec3| {
+            if (b - a).length() < 1e-6 {
+                fallback
+            } else {
+                b - a
+            }
+        };
+        let chord = p3 - p0;
+        let v0 = leg(p0, p1, leg(p0, p2, chord));
+        let v2 = leg(p2, p3, leg(p1, p3, chord));
+        let v1 = leg(p1, p2, (v0 + v2) / 2.0);
+        let normal = |v: Vec3| vec3(-v.z, 0.0, v.x).normalize_or_zero() * right;
+        let (n0, n1, n2) = (normal(v0), normal(v1), normal(v2));
+
+        let q0 = p0 + n0;
+        let q3 = p3 + n2;
+        // intersect the line (a + s * u) with (b + r * w) on the ground plane
+        let intersect = |a: Vec3, u: Vec3, b: Vec3, w: Vec3, fallback: Vec3| {
+            match LineByPQ::line(a, a + u).intersection_xz(&LineByPQ::line(b, b + w)) {
+                Intersection::Point { point, .. } => point,
+                // parallel legs
+                _ => fallback,
+            }
+        };
+        let q1 = intersect(q0, v0, p1 + n1, v1, p1 + n0);
+        let q2 = intersect(p2 + n1, v1, q3, v2, p2 + n2);
+        let up = Vec3::Y * top;
+        Self::new([q0 + up, q1 + up, q2 + up, q3 + up])
+    }
+}
+
+impl Segment for CubicBezierCurve {
+    fn position(&self, t: f32) -> Vec3 {
+        CubicBezierCurve::position(self, t)
+    }
+
+    fn tangent(&self, t: f32) -> Vec3 {
+        self.velocity(t)
+    }
+
+    fn acceleration(&self, t: f32) -> Vec3 {
+        let [p0, p1, p2, p3] = self.ctrl_pts;
+        6.0 * (1.0 - t) * (p2 - 2.0 * p1 + p0) + 6.0 * t * (p3 - 2.0 * p2 + p1)
+    }
+
+    fn length_of(&self, t: f32) -> f32 {
+        CubicBezierCurve::length_of(self, t)
+    }
+
+    fn split_at_t(&self, t: f32) -> (Self, Self) {
+        CubicBezierCurve::split_at_t(self, t)
+    }
+
+    fn reverse(&self) -> Self {
+        let [p0, p1, p2, p3] = self.ctrl_pts;
+        Self::new([p3, p2, p1, p0])
+    }
+
+    fn translate(&self, offset: Vec3) -> Self {
+        Self::new(self.ctrl_pts.map(|p| p + offset))
+    }
+
+    fn bbox(&self) -> Aabb {
+        CubicBezierCurve::bbox(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::vec3;
+
+    use super::*;
+
+    fn polyline_length(curve: &CubicBezierCurve, n: isize) -> f32 {
+        curve
+            .iter_positions(n)
+            .collect::<Vec<Vec3>>()
+            .windows(2)
+            .fold(0.0, |acc, p| acc + (p[0] - p[1]).length())
+    }
+
+    #[test]
+    fn test_cubic_curve_position() {
+        let curve = CubicBezierCurve::new([
+            vec3(0.0, 0.0, 0.0),
+            vec3(0.0, 0.0, 3.0),
+            vec3(3.0, 0.0, 3.0),
+            vec3(3.0, 0.0, 0.0),
+        ]);
+        assert_eq!(curve.position(0.0), curve.start());
+        assert_eq!(curve.position(1.0), curve.end());
+        assert_eq!(curve.position(0.5), vec3(1.5, 0.0, 2.25));
+    }
+
+    #[test]
+    fn test_cubic_curve_length() {
+        let curves = [
+            CubicBezierCurve::new([
+                vec3(-9., 0.0, -9.0),
+                vec3(-5.0, 0.0, 8.0),
+                vec3(5.0, 0.0, -9.0),
+                vec3(9., 0.0, -7.0),
+            ]),
+            // a straight line with uneven handles
+            CubicBezierCurve::new([
+                vec3(0.0, 0.0, 0.0),
+                vec3(0.1, 0.0, 0.0),
+                vec3(0.2, 0.0, 0.0),
+                vec3(4.0, 0.0, 0.0),
+            ]),
+            // cusp
+            CubicBezierCurve::new([
+                vec3(0.0, 0.0, 0.0),
+                vec3(2.0, 0.0, 2.0),
+                vec3(0.0, 0.0, 2.0),
+                vec3(2.0, 0.0, 0.0),
+            ]),
+        ];
+        for curve in curves.iter() {
+            let len = curve.length();
+            let real_len = polyline_length(curve, 4096);
+            assert!(
+                (len - real_len).abs() < 1e-3,
+                "curve length: {:?}, real length: {:?}",
+                len,
+                real_len,
+            );
+        }
+    }
+
+    #[test]
+    fn test_cubic_split() {
+        let curve = CubicBezierCurve::new([
+            vec3(-9., 0.0, -9.0),
+            vec3(-5.0, 0.0, 8.0),
+            vec3(5.0, 0.0, -9.0),
+            vec3(9., 0.0, -7.0),
+        ]);
+        let (a, b) = curve.split_at_t(0.3);
+        assert!((a.end() - curve.position(0.3)).length() < 1e-5);
+        assert!((a.position(0.5) - curve.position(0.15)).length() < 1e-5);
+        assert!((b.position(0.5) - curve.position(0.65)).length() < 1e-5);
+        assert!((a.length() + b.length() - curve.length()).abs() < 1e-3);
+        assert!((curve.length_of(0.3) - a.length()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_cubic_offset() {
+        let curve = CubicBezierCurve::new([
+            vec3(0.0, 0.0, 0.0),
+            vec3(0.0, 0.0, 5.0),
+            vec3(5.0, 0.0, 10.0),
+            vec3(10.0, 0.0, 10.0),
+        ]);
+        let offset = curve.to_curve2(3.0, 1e2).offset(1.0, 0.0);
+        for pt in offset.iter_positions(64) {
+            let dist = curve.distance_to(pt);
+            assert!((dist - 1.0).abs() < 2e-2, "distance: {:?}", dist);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! plain geometry out of curves: polylines within a flatness tolerance, and SVG paths seen
+//! from above, with x to the right and z downwards.
+
+use std::fmt::Write;
+
+use bevy::math::{Vec2, Vec3};
+
+use super::{segment::Segment, Curve, CurveSegment};
+
+/// deepest halving when flattening a single segment
+const MAX_DEPTH: usize = 16;
+
+fn distance_to_chord(pt: Vec3, a: Vec3, b: Vec3) -> f32 {
+    let chord = b - a;
+    let len_sq = chord.length_squared();
+    if len_sq < 1e-12 {
+        return (pt - a).length();
+    }
+    let t = ((pt - a).dot(chord) / len_sq).clamp(0.0, 1.0);
+    (pt - (a + chord * t)).length()
+}
+
+/// push the samples after |t0| up to and including |t1|, so that every point of the segment
+/// between them is within |tolerance| of the polyline through them
+fn flatten_range(
+    segment: &CurveSegment,
+    (t0, p0): (f32, Vec3),
+    (t1, p1): (f32, Vec3),
+    tolerance: f32,
+    depth: usize,
+    out: &mut Vec<(f32, Vec3)>,
+) {
+    let flat = || {
+        // the middle alone misses s-shaped pieces
+        [0.25, 0.5, 0.75].iter().all(|u| {
+            let pt = segment.position(t0 + (t1 - t0) * u);
+            distance_to_chord(pt, p0, p1) <= tolerance
+        })
+    };
+    if depth == 0 || flat() {
+        out.push((t1, p1));
+        return;
+    }
+    let tm = (t0 + t1) / 2.0;
+    let pm = segment.position(tm);
+    flatten_range(segment, (t0, p0), (tm, pm), tolerance, depth - 1, out);
+    flatten_range(segment, (tm, pm), (t1, p1), tolerance, depth - 1, out);
+}
+
+/// (arc length, point) at the vertices of a polyline that stays within |tolerance| meters
+/// of |curve|. the chord height decides where to sample: straight pieces get only their
+/// ends, sharp turns as many points as they need. the first and the last vertex are exactly
+/// the ends of the curve.
+pub fn flatten(curve: &Curve, tolerance: f32) -> Vec<(f32, Vec3)> {
+    let mut out = vec![(0.0, curve.start())];
+    let mut samples = vec![];
+    for (i, segment) in curve.curves.iter().enumerate() {
+        let prefix_len = if i == 0 {
+            0.0
+        } else {
+            curve.sum_lengths[i - 1]
+        };
+        let (start, end) = (segment.start(), segment.end());
+        if (start - out.last().unwrap().1).length() > 1e-6 {
+            out.push((prefix_len, start));
+        }
+        samples.clear();
+        flatten_range(
+            segment,
+            (0.0, start),
+            (1.0, end),
+            tolerance,
+            MAX_DEPTH,
+            &mut samples,
+        );
+        out.extend(samples.iter().map(|&(t, p)| {
+            let length = if t == 1.0 {
+                curve.sum_lengths[i]
+            } else {
+                prefix_len + segment.length_of(t)
+            };
+            (length, p)
+        }));
+    }
+    out
+}
+
+/// the vertices of a polyline that stays within |tolerance| meters of |curve|
+pub fn polyline(curve: &Curve, tolerance: f32) -> Vec<Vec3> {
+    flatten(curve, tolerance)
+        .into_iter()
+        .map(|(_, p)| p)
+        .collect()
+}
+
+fn xz(p: Vec3) -> String {
+    format!("{} {}", p.x, p.z)
+}
+
+/// the `d` attribute of an SVG path tracing |curve| from above. Bézier segments are written
+/// as they are, arcs and clothoids as the cubics that approximate them.
+pub fn svg_path(curve: &Curve) -> String {
+    let mut d = format!("M {}", xz(curve.start()));
+    let mut last = curve.start();
+    for segment in curve.curves.iter() {
+        if (segment.start() - last).length() > 1e-4 {
+            write!(d, " M {}", xz(segment.start())).unwrap();
+        }
+        match segment {
+            CurveSegment::Line(c) => write!(d, " L {}", xz(c.ctrl_pts[1])).unwrap(),
+            CurveSegment::Quadratic(c) => {
+                write!(d, " Q {} {}", xz(c.ctrl_pts[1]), xz(c.ctrl_pts[2])).unwrap()
+            }
+            CurveSegment::Cubic(c) => write_cubic(&mut d, c.ctrl_pts),
+            CurveSegment::Arc(c) => c
+                .to_cubics()
+                .iter()
+                .for_each(|c| write_cubic(&mut d, c.ctrl_pts)),
+            CurveSegment::Clothoid(c) => c
+                .to_cubics()
+                .iter()
+                .for_each(|c| write_cubic(&mut d, c.ctrl_pts)),
+        }
+        last = segment.end();
+    }
+    d
+}
+
+fn write_cubic(d: &mut String, [_, p1, p2, p3]: [Vec3; 4]) {
+    write!(d, " C {} {} {}", xz(p1), xz(p2), xz(p3)).unwrap();
+}
+
+/// a standalone SVG document with one stroked path per curve, framed around all of them
+pub fn svg_document(curves: &[Curve], stroke_width: f32) -> String {
+    let (min, max) = curves
+        .iter()
+        .flat_map(|c| polyline(c, stroke_width / 4.0))
+        .map(|p| Vec2::new(p.x, p.z))
+        .fold(
+            (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+            |(min, max), p| (min.min(p), max.max(p)),
+        );
+    let (min, max) = if min.x > max.x {
+        (Vec2::ZERO, Vec2::ZERO)
+    } else {
+        (min - stroke_width * 2.0, max + stroke_width * 2.0)
+    };
+    let size = max - min;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n",
+        min.x, min.y, size.x, size.y
+    );
+    for curve in curves {
+        writeln!(
+            svg,
+            "  <path d=\"{}\" fill=\"none\" stroke=\"black\" stroke-width=\"{}\"/>",
+            svg_path(curve),
+            stroke_width
+        )
+        .unwrap();
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
@@ -0,0 +1,227 @@
+//! curve-curve intersection by Bézier subdivision.
+//!
+//! every segment is converted to Bézier pieces (lines, arcs and clothoids are exact or close
+//! approximations), then pairs of pieces are split recursively. a pair is dropped as soon as
+//! the bounding boxes of their control points stop overlapping, which is safe thanks to the
+//! convex hull property. flat enough pieces are intersected as straight chords.
+
+use bevy::math::{vec2, Vec2, Vec3};
+
+use super::{segment::Segment, CurveSegment};
+
+/// pieces closer than this (in meters) are considered to touch
+const TOLERANCE: f32 = 1e-3;
+const MAX_DEPTH: usize = 40;
+
+#[derive(Clone, Debug)]
+struct BezierPiece {
+    /// control points projected to the ground plane
+    pts: Vec<Vec2>,
+    /// index of the segment this piece comes from
+    index: usize,
+    t0: f32,
+    t1: f32,
+}
+
+impl BezierPiece {
+    fn new(pts: &[Vec3], index: usize, t0: f32, t1: f32) -> Self {
+        Self {
+            pts: pts.iter().map(|p| vec2(p.x, p.z)).collect(),
+            index,
+            t0,
+            t1,
+        }
+    }
+
+    fn bbox(&self) -> (Vec2, Vec2) {
+        self.pts.iter().fold(
+            (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+            |(min, max), p| (min.min(*p), max.max(*p)),
+        )
+    }
+
+    /// max distance from the inner control points to the chord
+    fn flatness(&self) -> f32 {
+        let (p, q) = (self.pts[0], *self.pts.last().unwrap());
+        let chord = q - p;
+        let len = chord.length();
+        self.pts[1..self.pts.len() - 1]
+            .iter()
+            .map(|c| {
+                if len < 1e-9 {
+                    (*c - p).length()
+                } else {
+                    (*c - p).perp_dot(chord).abs() / len
+                }
+            })
+            .fold(0.0, f32::max)
+    }
+
+    /// de Casteljau split at the middle
+    fn split(&self) -> (Self, Self) {
+        let mut left = vec![self.pts[0]];
+        let mut right = vec![*self.pts.last().unwrap()];
+        let mut pts = self.pts.clone();
+        while pts.len() > 1 {
+            pts = pts.windows(2).map(|w| (w[0] + w[1]) / 2.0).collect();
+            left.push(pts[0]);
+            right.push(*pts.last().unwrap());
+        }
+        right.reverse();
+        let mid = (self.t0 + self.t1) / 2.0;
+        (
+            Self {
+                pts: left,
+                index: self.index,
+                t0: self.t0,
+                t1: mid,
+            },
+            Self {
+                pts: right,
+                index: self.index,
+                t0: mid,
+                t1: self.t1,
+            },
+        )
+    }
+}
+
+fn to_pieces(index: usize, segment: &CurveSegment) -> Vec<BezierPiece> {
+    let split_evenly = |ctrl_pts: Vec<Vec<Vec3>>| {
+        let n = ctrl_pts.len() as f32;
+        ctrl_pts
+            .iter()
+            .enumerate()
+            .map(|(i, pts)| BezierPiece::new(pts, index, i as f32 / n, (i + 1) as f32 / n))
+            .collect()
+    };
+    match segment {
+        CurveSegment::Line(c) => vec![BezierPiece::new(&c.ctrl_pts, index, 0., 1.)],
+        CurveSegment::Quadratic(c) => vec![BezierPiece::new(&c.ctrl_pts, index, 0., 1.)],
+        CurveSegment::Cubic(c) => vec![BezierPiece::new(&c.ctrl_pts, index, 0., 1.)],
+        CurveSegment::Arc(c) => {
+            split_evenly(c.to_cubics().iter().map(|c| c.ctrl_pts.to_vec()).collect())
+        }
+        CurveSegment::Clothoid(c) => {
+            split_evenly(c.to_cubics().iter().map(|c| c.ctrl_pts.to_vec()).collect())
+        }
+    }
+}
+
+/// closest points of the segments p0-p1 and q0-q1, as the parameters on each segment
+fn closest_on_chords(p0: Vec2, p1: Vec2, q0: Vec2, q1: Vec2) -> (f32, f32) {
+    let (d1, d2, r) = (p1 - p0, q1 - q0, p0 - q0);
+    let (a, e, f) = (d1.length_squared(), d2.length_squared(), d2.dot(r));
+    if a < 1e-12 && e < 1e-12 {
+        return (0.0, 0.0);
+    }
+    if a < 1e-12 {
+        return (0.0, (f / e).clamp(0.0, 1.0));
+    }
+    let c = d1.dot(r);
+    if e < 1e-12 {
+        return ((-c / a).clamp(0.0, 1.0), 0.0);
+    }
+    let b = d1.dot(d2);
+    let denom = a * e - b * b;
+    let mut s = if denom > 1e-12 {
+        ((b * f - c * e) / denom).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let mut t = (b * s + f) / e;
+    if t < 0.0 {
+        t = 0.0;
+        s = (-c / a).clamp(0.0, 1.0);
+    } else if t > 1.0 {
+        t = 1.0;
+        s = ((b - c) / a).clamp(0.0, 1.0);
+    }
+    (s, t)
+}
+
+fn intersect_pieces(
+    a: &BezierPiece,
+    b: &BezierPiece,
+    depth: usize,
+    hits: &mut Vec<(usize, f32, usize, f32)>,
+) {
+    let (a_min, a_max) = a.bbox();
+    let (b_min, b_max) = b.bbox();
+    if a_min.x > b_max.x + TOLERANCE
+        || b_min.x > a_max.x + TOLERANCE
+        || a_min.y > b_max.y + TOLERANCE
+        || b_min.y > a_max.y + TOLERANCE
+    {
+        return;
+    }
+    let a_flat = a.flatness() < TOLERANCE / 4.0;
+    let b_flat = b.flatness() < TOLERANCE / 4.0;
+    if (a_flat && b_flat) || depth >= MAX_DEPTH {
+        let (a0, a1) = (a.pts[0], *a.pts.last().unwrap());
+        let (b0, b1) = (b.pts[0], *b.pts.last().unwrap());
+        let (s, t) = closest_on_chords(a0, a1, b0, b1);
+        if (a0.lerp(a1, s) - b0.lerp(b1, t)).length() < TOLERANCE {
+            hits.push((
+                a.index,
+                a.t0 + s * (a.t1 - a.t0),
+                b.index,
+                b.t0 + t * (b.t1 - b.t0),
+            ));
+        }
+        return;
+    }
+    // split the bigger piece
+    let size = |min: Vec2, max: Vec2| (max - min).length_squared();
+    if !a_flat && (b_flat || size(a_min, a_max) >= size(b_min, b_max)) {
+        let (l, r) = a.split();
+        intersect_pieces(&l, b, depth + 1, hits);
+        intersect_pieces(&r, b, depth + 1, hits);
+    } else {
+        let (l, r) = b.split();
+        intersect_pieces(a, &l, depth + 1, hits);
+        intersect_pieces(a, &r, depth + 1, hits);
+    }
+}
+
+/// all intersections of two chains of segments on the ground plane.
+/// return (segment index of a, t on it, segment index of b, t on it)
+pub(super) fn intersections(
+    a: &[CurveSegment],
+    b: &[CurveSegment],
+) -> Vec<(usize, f32, usize, f32)> {
+    // only segments whose boxes overlap on the ground can cross
+    let a_boxes = a.iter().map(|s| s.bbox().expand(TOLERANCE)).collect::<Vec<_>>();
+    let b_boxes = b.iter().map(|s| s.bbox().expand(TOLERANCE)).collect::<Vec<_>>();
+    let near = |ia: usize| b_boxes.iter().any(|bb| bb.intersects_xz(&a_boxes[ia]));
+    let a_pieces = a
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| near(*i))
+        .flat_map(|(i, s)| to_pieces(i, s))
+        .collect::<Vec<_>>();
+    let b_pieces = b
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| a_boxes.iter().any(|ab| ab.intersects_xz(&b_boxes[*i])))
+        .flat_map(|(i, s)| to_pieces(i, s))
+        .collect::<Vec<_>>();
+    let mut hits = vec![];
+    for pa in a_pieces.iter() {
+        for pb in b_pieces.iter() {
+            if a_boxes[pa.index].intersects_xz(&b_boxes[pb.index]) {
+                intersect_pieces(pa, pb, 0, &mut hits);
+            }
+        }
+    }
+    // arcs and clothoids were approximated, pull the points back onto the real segments
+    hits.iter_mut().for_each(|(ia, ta, ib, tb)| {
+        if matches!(a[*ia], CurveSegment::Arc(_) | CurveSegment::Clothoid(_)) {
+            *ta = a[*ia].closest_t(b[*ib].position(*tb));
+        }
+        if matches!(b[*ib], CurveSegment::Arc(_) | CurveSegment::Clothoid(_)) {
+            *tb = b[*ib].closest_t(a[*ia].position(*ta));
+        }
+    });
+    hits
+}
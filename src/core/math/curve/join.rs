@@ -0,0 +1,146 @@
+//! smooth connections between curves on the ground plane: fillets of a given radius and
+//! tangent-continuous joins that keep the turn radius above a minimum.
+
+use bevy::math::{vec3, Vec3};
+
+use super::{arc::ArcSegment, cubic::CubicBezierCurve, line::LineSegment, Curve};
+use crate::core::math::error::{GeometryError, Result};
+
+/// the two trimmed curves and the arc that replaces the corner between them
+#[derive(Debug, Clone)]
+pub struct Fillet {
+    /// the first curve up to the arc, None if the arc starts at its very beginning
+    pub before: Option<Curve>,
+    pub arc: Curve,
+    /// the second curve after the arc, None if the arc ends at its very end
+    pub after: Option<Curve>,
+}
+
+impl Fillet {
+    /// before, arc and after as one curve
+    pub fn to_curve(&self) -> Curve {
+        let parts = [self.before.as_ref(), Some(&self.arc), self.after.as_ref()];
+        let curves = parts
+            .into_iter()
+            .flatten()
+            .flat_map(|curve| curve.curves.iter().cloned())
+            .collect();
+        Curve::build(curves, self.arc.tolerance)
+    }
+}
+
+/// round the corner where |a| crosses |b| with an arc of |radius| tangent to both. the
+/// first crossing along |a| is used; |a| is kept before it and |b| after it.
+pub fn fillet(a: &Curve, b: &Curve, radius: f32) -> Result<Fillet> {
+    const EPS: f32 = 1e-4;
+    if radius <= 0.0 {
+        return Err(GeometryError::InvalidArgument {
+            name: "radius",
+            value: radius,
+        });
+    }
+    let Some(&(ta, tb, corner)) = a.intersections(b).first() else {
+        return Err(GeometryError::NoSolution("crossing of the curves"));
+    };
+    let (da, db) = (a.velocity(ta), b.velocity(tb));
+    let turn = da.x * db.z - da.z * db.x;
+    if turn.abs() < EPS {
+        return Err(GeometryError::Degenerate {
+            what: "corner, the curves are tangent",
+            points: vec![corner],
+        });
+    }
+    // the center is on the inside of the turn, |radius| away from both curves
+    let side = turn.signum() * radius;
+    let center = a
+        .offset(side, 0.0)
+        .intersections(&b.offset(side, 0.0))
+        .into_iter()
+        .map(|(_, _, pt)| pt)
+        .min_by(|p, q| (*p - corner).length().total_cmp(&(*q - corner).length()))
+        .ok_or(GeometryError::NoSolution("fillet of that radius"))?;
+    let (la, lb) = (a.project(center).length, b.project(center).length);
+    if la > ta * a.length() + EPS || lb < tb * b.length() - EPS {
+        return Err(GeometryError::NoSolution("fillet of that radius"));
+    }
+    let (start, end) = (a.position_at_length(la), b.position_at_length(lb));
+    let arc =
+        ArcSegment::from_tangent(start, a.velocity(la / a.length()), end).ok_or_else(|| {
+            GeometryError::Degenerate {
+                what: "fillet",
+                points: vec![start, corner, end],
+            }
+        })?;
+    Ok(Fillet {
+        before: (la > EPS).then(|| a.slice_by_length(0.0, la)).transpose()?,
+        arc: arc.to_curve().with_tolerance(a.tolerance),
+        after: (b.length() - lb > EPS)
+            .then(|| b.slice_by_length(lb, b.length()))
+            .transpose()?,
+    })
+}
+
+/// the smallest radius |curve| turns with, infinite for straight curves
+fn turn_radius(curve: &Curve) -> f32 {
+    let n = 16 * curve.curves.len();
+    let k = (0..=n)
+        .map(|i| curve.curvature(i as f32 / n as f32).abs())
+        .fold(0.0, f32::max);
+    1.0 / k
+}
+
+/// a curve from |p| heading |v| to |q| heading |u| that never turns tighter than
+/// |min_radius|. tries, in order: a clothoid alignment, a single arc between the tangents,
+/// and the cubic Hermite curve whose handles give the widest turn (for S bends).
+pub fn from_tangents(p: Vec3, v: Vec3, q: Vec3, u: Vec3, min_radius: f32) -> Result<Curve> {
+    if let Ok(curve) = Curve::from_clothoid_alignment(p, v, q, u, 0.5) {
+        if turn_radius(&curve) >= min_radius {
+            return Ok(curve);
+        }
+    }
+    if let Some(curve) = widest_arc(p, v, q, u).filter(|c| turn_radius(c) >= min_radius) {
+        return Ok(curve);
+    }
+    let (d0, d1) = (v.normalize_or_zero(), u.normalize_or_zero());
+    let dist = (q - p).length();
+    let (radius, curve) = (1..=16)
+        .map(|i| {
+            let handle = dist * i as f32 / 16.0;
+            CubicBezierCurve::new([p, p + d0 * handle, q - d1 * handle, q]).to_curve()
+        })
+        .map(|curve| (turn_radius(&curve), curve))
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap();
+    if radius < min_radius {
+        return Err(GeometryError::TooTight {
+            min_radius,
+            best: radius,
+        });
+    }
+    Ok(curve)
+}
+
+/// the fillet of the two tangent rays with the largest radius that fits, None if the
+/// tangents don't meet ahead of both points
+fn widest_arc(p: Vec3, v: Vec3, q: Vec3, u: Vec3) -> Option<Curve> {
+    let d0 = vec3(v.x, 0.0, v.z).normalize_or_zero();
+    let d1 = vec3(u.x, 0.0, u.z).normalize_or_zero();
+    let cross = d0.x * d1.z - d0.z * d1.x;
+    if cross.abs() < 1e-4 {
+        return None;
+    }
+    // solve p + a * d0 = q - b * d1 for the tangent intersection
+    let w = q - p;
+    let a = (w.x * d1.z - w.z * d1.x) / cross;
+    let b = (d0.x * w.z - d0.z * w.x) / cross;
+    if a <= 0.0 || b <= 0.0 {
+        return None;
+    }
+    let radius = a.min(b) / (d0.angle_between(d1) / 2.0).tan();
+    let reach = a.max(b) * 2.0;
+    let ray_in = LineSegment::new([p, p + d0 * reach]).to_curve();
+    let ray_out = LineSegment::new([q - d1 * reach, q]).to_curve();
+    fillet(&ray_in, &ray_out, radius * (1.0 - 1e-3))
+        .ok()
+        .map(|fillet| fillet.to_curve())
+}
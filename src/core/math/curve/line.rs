@@ -0,0 +1,115 @@
+use bevy::math::{vec3, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::core::math::{
+    aabb::Aabb,
+    error::{check_finite, GeometryError, Result},
+};
+
+use super::{segment::Segment, Curve, CurveSegment};
+
+/// a straight segment from ctrl_pts[0] to ctrl_pts[1]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LineSegment {
+    pub ctrl_pts: [Vec3; 2],
+}
+
+impl LineSegment {
+    pub fn new(ctrl_pts: [Vec3; 2]) -> Self {
+        Self { ctrl_pts }
+    }
+
+    /// fails on non-finite or coincident points
+    pub fn try_new(ctrl_pts: [Vec3; 2]) -> Result<Self> {
+        check_finite("line", &ctrl_pts)?;
+        if ctrl_pts[0] == ctrl_pts[1] {
+            return Err(GeometryError::Degenerate {
+                what: "line",
+                points: ctrl_pts.to_vec(),
+            });
+        }
+        Ok(Self::new(ctrl_pts))
+    }
+
+    pub fn to_curve(&self) -> Curve {
+        Curve::from_segments(vec![CurveSegment::Line(self.clone())])
+    }
+
+    /// shift the segment by |right| on the ground plane and |top| upwards
+    pub fn offset(&self, right: f32, top: f32) -> Self {
+        let [p, q] = self.ctrl_pts;
+        let v = q - p;
+        let n = vec3(-v.z, 0.0, v.x).normalize_or_zero() * right + Vec3::Y * top;
+        Self::new([p + n, q + n])
+    }
+}
+
+impl Segment for LineSegment {
+    fn position(&self, t: f32) -> Vec3 {
+        self.ctrl_pts[0].lerp(self.ctrl_pts[1], t)
+    }
+
+    fn tangent(&self, _t: f32) -> Vec3 {
+        self.ctrl_pts[1] - self.ctrl_pts[0]
+    }
+
+    fn acceleration(&self, _t: f32) -> Vec3 {
+        Vec3::ZERO
+    }
+
+    fn length_of(&self, t: f32) -> f32 {
+        (self.ctrl_pts[1] - self.ctrl_pts[0]).length() * t
+    }
+
+    fn split_at_t(&self, t: f32) -> (Self, Self) {
+        let mid = self.position(t);
+        (
+            Self::new([self.ctrl_pts[0], mid]),
+            Self::new([mid, self.ctrl_pts[1]]),
+        )
+    }
+
+    fn reverse(&self) -> Self {
+        Self::new([self.ctrl_pts[1], self.ctrl_pts[0]])
+    }
+
+    fn translate(&self, offset: Vec3) -> Self {
+        Self::new(self.ctrl_pts.map(|p| p + offset))
+    }
+
+    fn bbox(&self) -> Aabb {
+        Aabb::from_points(self.ctrl_pts)
+    }
+
+    fn closest_t(&self, pt: Vec3) -> f32 {
+        let v = self.tangent(0.0);
+        let len_sq = v.length_squared();
+        if len_sq < 1e-12 {
+            return 0.0;
+        }
+        ((pt - self.ctrl_pts[0]).dot(v) / len_sq).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::vec3;
+
+    use super::*;
+
+    #[test]
+    fn test_line_segment() {
+        let line = LineSegment::new([vec3(0.0, 0.0, 0.0), vec3(4.0, 0.0, 3.0)]);
+        assert_eq!(line.length(), 5.0);
+        assert_eq!(line.position(0.5), vec3(2.0, 0.0, 1.5));
+        assert_eq!(line.closest_t(vec3(-3.0, 0.0, 0.0)), 0.0);
+        assert!((line.distance_to(vec3(4.0, 0.0, -2.0)) - 4.0).abs() < 1e-5);
+
+        let (a, b) = line.split_at_t(0.2);
+        assert!((a.length() - 1.0).abs() < 1e-5);
+        assert!((b.length() - 4.0).abs() < 1e-5);
+
+        let offset = line.offset(1.0, 0.5);
+        assert!((line.distance_to(offset.start() - Vec3::Y * 0.5) - 1.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,192 @@
+//! offset curves with an error bound.
+//!
+//! lines and horizontal arcs have exact offsets. everything else is fitted with cubic
+//! Hermite pieces that match the exact offset point and derivative at both ends; a piece is
+//! halved until it stays within the tolerance of the exact offset at its inner samples.
+
+use bevy::math::{vec3, Vec3};
+use serde::{Deserialize, Serialize};
+
+use super::{cubic::CubicBezierCurve, line::LineSegment, segment::Segment, CurveSegment};
+
+/// deepest halving of a single piece
+pub(super) const MAX_DEPTH: usize = 16;
+/// where each fitted piece is compared with the exact curve
+const CHECKS: [f32; 3] = [0.25, 0.5, 0.75];
+
+/// how far to the right of the curve the offset goes, as a function of the arc length.
+/// the width is linear between stops and constant before the first and after the last one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WidthProfile {
+    /// (arc length, width) sorted by arc length
+    stops: Vec<(f32, f32)>,
+}
+
+impl WidthProfile {
+    /// |stops| are (arc length, width to the right) pairs. an empty profile is 0 everywhere.
+    pub fn new(mut stops: Vec<(f32, f32)>) -> Self {
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if stops.is_empty() {
+            stops.push((0.0, 0.0));
+        }
+        Self { stops }
+    }
+
+    pub fn constant(width: f32) -> Self {
+        Self::new(vec![(0.0, width)])
+    }
+
+    /// taper from |from| at the start to |to| at |length|
+    pub fn linear(length: f32, from: f32, to: f32) -> Self {
+        Self::new(vec![(0.0, from), (length, to)])
+    }
+
+    pub fn at(&self, length: f32) -> f32 {
+        let i = self.stops.partition_point(|s| s.0 <= length);
+        if i == 0 {
+            return self.stops[0].1;
+        }
+        if i == self.stops.len() {
+            return self.stops[i - 1].1;
+        }
+        let ((l0, w0), (l1, w1)) = (self.stops[i - 1], self.stops[i]);
+        w0 + (w1 - w0) * (length - l0) / (l1 - l0)
+    }
+
+    /// the derivative of the width at |length|, from the piece that starts there
+    pub fn slope(&self, length: f32) -> f32 {
+        let i = self.stops.partition_point(|s| s.0 <= length);
+        if i == 0 || i == self.stops.len() {
+            return 0.0;
+        }
+        let ((l0, w0), (l1, w1)) = (self.stops[i - 1], self.stops[i]);
+        (w1 - w0) / (l1 - l0)
+    }
+
+    /// stops strictly between |start| and |end|, where the width has a kink
+    pub(super) fn breaks(&self, start: f32, end: f32) -> impl Iterator<Item = f32> + '_ {
+        self.stops
+            .iter()
+            .map(|s| s.0)
+            .filter(move |&l| l > start && l < end)
+    }
+}
+
+/// the exact offset of a segment, indexed by the arc length on that segment
+struct ExactOffset<'a> {
+    segment: &'a CurveSegment,
+    profile: &'a WidthProfile,
+    /// arc length of the curve before this segment, to look up the profile
+    prefix_len: f32,
+    top: f32,
+}
+
+impl ExactOffset<'_> {
+    /// the offset point and its derivative with respect to the arc length of the segment
+    fn at(&self, length: f32) -> (Vec3, Vec3) {
+        let t = self.segment.t_of_length(length);
+        let d = self.segment.tangent(t);
+        let speed = d.length();
+        let horizontal = vec3(d.x, 0.0, d.z);
+        let h_len = horizontal.length();
+        let width = self.profile.at(self.prefix_len + length);
+        let position = self.segment.position(t) + Vec3::Y * self.top;
+        if speed < 1e-9 || h_len < 1e-9 {
+            return (position, d.normalize_or_zero());
+        }
+        let normal = vec3(-d.z, 0.0, d.x) / h_len;
+        // the normal turns with the heading, at the rate the heading changes on the ground
+        let a = self.segment.acceleration(t);
+        let turn_rate = (d.x * a.z - d.z * a.x) / (h_len * h_len);
+        let normal_derivative = -turn_rate / speed * horizontal / h_len;
+        (
+            position + normal * width,
+            d / speed
+                + normal_derivative * width
+                + normal * self.profile.slope(self.prefix_len + length),
+        )
+    }
+}
+
+/// fit the exact curve |at| on [start, end] with cubics and push them to |rets|. |at| gives
+/// the point and its derivative at an arc length of the underlying segment.
+pub(super) fn fit_hermite(
+    at: &dyn Fn(f32) -> (Vec3, Vec3),
+    start: f32,
+    end: f32,
+    tolerance: f32,
+    depth: usize,
+    rets: &mut Vec<CurveSegment>,
+) {
+    let (p0, d0) = at(start);
+    let (p3, d3) = at(end);
+    let handle = (end - start) / 3.0;
+    let cubic = CubicBezierCurve::new([p0, p0 + d0 * handle, p3 - d3 * handle, p3]);
+    let error = CHECKS
+        .iter()
+        .map(|&u| (cubic.position(u) - at(start + (end - start) * u).0).length())
+        .fold(0.0, f32::max);
+    if error <= tolerance || depth == 0 {
+        rets.push(cubic.into());
+        return;
+    }
+    let mid = (start + end) / 2.0;
+    fit_hermite(at, start, mid, tolerance, depth - 1, rets);
+    fit_hermite(at, mid, end, tolerance, depth - 1, rets);
+}
+
+/// offset |segment| by |profile| (shifted by |prefix_len|) and |top| upwards, within
+/// |tolerance| meters of the exact offset
+pub(super) fn offset_segment(
+    segment: &CurveSegment,
+    prefix_len: f32,
+    profile: &WidthProfile,
+    top: f32,
+    tolerance: f32,
+) -> Vec<CurveSegment> {
+    let length = segment.length();
+    let mut cuts = vec![0.0];
+    cuts.extend(
+        profile
+            .breaks(prefix_len, prefix_len + length)
+            .map(|l| l - prefix_len),
+    );
+    cuts.push(length);
+
+    let exact = ExactOffset {
+        segment,
+        profile,
+        prefix_len,
+        top,
+    };
+    let mut rets = vec![];
+    for piece in cuts.windows(2) {
+        let (start, end) = (piece[0], piece[1]);
+        let (w0, w1) = (profile.at(prefix_len + start), profile.at(prefix_len + end));
+        match segment {
+            // a linear width along a line is still a line
+            CurveSegment::Line(_) => {
+                rets.push(LineSegment::new([exact.at(start).0, exact.at(end).0]).into());
+            }
+            CurveSegment::Arc(arc) if arc.is_horizontal() && w0 == w1 => {
+                let (t0, t1) = (start / length, end / length);
+                let arc = arc.split_at_t(t1).0;
+                let arc = if t1 > 0.0 {
+                    arc.split_at_t(t0 / t1).1
+                } else {
+                    arc
+                };
+                rets.push(arc.offset(w0, top).into());
+            }
+            _ => fit_hermite(
+                &|length| exact.at(length),
+                start,
+                end,
+                tolerance,
+                MAX_DEPTH,
+                &mut rets,
+            ),
+        }
+    }
+    rets
+}
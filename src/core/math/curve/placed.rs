@@ -0,0 +1,197 @@
+//! curves on maps too large for f32 world coordinates.
+//!
+//! an f32 has about 7 significant digits: kilometres away from (0, 0, 0), points are only
+//! good to a millimetre and everything computed from them is worse. a [`PlacedCurve`] keeps
+//! an f64 origin and its [`Curve`] in f32 around that origin, so the geometry is as exact as
+//! near (0, 0, 0) wherever it is on the map. simulation works with world positions in
+//! [`DVec3`]; rendering gets the curve relative to a [`FloatingOrigin`] near the camera.
+
+use bevy::math::{DVec3, Vec3};
+use serde::{Deserialize, Serialize};
+
+use super::{line::LineSegment, quadratic::QuadraticBezierCurve, Curve, Projection};
+use crate::core::math::{
+    aabb::Aabb,
+    error::{check_finite, Result},
+};
+
+/// a [`Curve`] in coordinates relative to an f64 origin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlacedCurve {
+    origin: DVec3,
+    local: Curve,
+}
+
+impl PlacedCurve {
+    pub fn new(origin: DVec3, local: Curve) -> Self {
+        Self { origin, local }
+    }
+
+    /// the curve |build| makes out of |pts|, which it gets relative to the first one
+    pub fn from_world<const N: usize>(
+        pts: [DVec3; N],
+        build: impl FnOnce([Vec3; N]) -> Result<Curve>,
+    ) -> Result<Self> {
+        let origin = pts.first().copied().unwrap_or(DVec3::ZERO);
+        let local = pts.map(|p| (p - origin).as_vec3());
+        check_finite("placed curve", &local)?;
+        Ok(Self::new(origin, build(local)?))
+    }
+
+    pub fn line(ctrl_pts: [DVec3; 2]) -> Result<Self> {
+        Self::from_world(ctrl_pts, |pts| Ok(LineSegment::try_new(pts)?.to_curve()))
+    }
+
+    pub fn quadratic(ctrl_pts: [DVec3; 3]) -> Result<Self> {
+        Self::from_world(ctrl_pts, |pts| {
+            Ok(QuadraticBezierCurve::try_new(pts)?.to_curve())
+        })
+    }
+
+    pub fn origin(&self) -> DVec3 {
+        self.origin
+    }
+
+    /// the curve around [`Self::origin`]
+    pub fn local(&self) -> &Curve {
+        &self.local
+    }
+
+    pub fn to_local(&self, pt: DVec3) -> Vec3 {
+        (pt - self.origin).as_vec3()
+    }
+
+    pub fn to_world(&self, pt: Vec3) -> DVec3 {
+        self.origin + pt.as_dvec3()
+    }
+
+    pub fn length(&self) -> f64 {
+        self.local.length() as f64
+    }
+
+    pub fn start(&self) -> DVec3 {
+        self.to_world(self.local.start())
+    }
+
+    pub fn end(&self) -> DVec3 {
+        self.to_world(self.local.end())
+    }
+
+    /// t is the fraction of the arc length, as in [`Curve::position`]
+    pub fn position(&self, t: f64) -> DVec3 {
+        self.position_at_length(t * self.length())
+    }
+
+    pub fn position_at_length(&self, length: f64) -> DVec3 {
+        self.to_world(self.local.position_at_length(length as f32))
+    }
+
+    /// directions don't depend on the origin, so they stay in f32
+    pub fn velocity(&self, t: f64) -> Vec3 {
+        self.local.velocity(t as f32)
+    }
+
+    pub fn project(&self, pt: DVec3) -> Projection {
+        self.local.project(self.to_local(pt))
+    }
+
+    /// the box around the curve in f32 world coordinates. good enough to find what is near,
+    /// measure with the curve itself.
+    pub fn bbox(&self) -> Aabb {
+        self.local.bbox().placed_at(self.origin)
+    }
+
+    /// the same curve around |origin|, e.g. to make the origins of two curves match
+    pub fn rebase(&self, origin: DVec3) -> Self {
+        let local = self.local.translate((self.origin - origin).as_vec3());
+        Self::new(origin, local)
+    }
+
+    /// the curve in render coordinates
+    pub fn relative_to(&self, floating: &FloatingOrigin) -> Curve {
+        self.rebase(floating.origin).local
+    }
+}
+
+/// the world position that rendering puts at (0, 0, 0). it follows the camera in steps of
+/// at least `max_distance`, so that everything on screen stays close to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FloatingOrigin {
+    origin: DVec3,
+    max_distance: f64,
+}
+
+impl FloatingOrigin {
+    pub fn new(max_distance: f64) -> Self {
+        Self {
+            origin: DVec3::ZERO,
+            max_distance,
+        }
+    }
+
+    pub fn origin(&self) -> DVec3 {
+        self.origin
+    }
+
+    pub fn to_render(&self, pt: DVec3) -> Vec3 {
+        (pt - self.origin).as_vec3()
+    }
+
+    pub fn to_world(&self, pt: Vec3) -> DVec3 {
+        self.origin + pt.as_dvec3()
+    }
+
+    /// move the origin to |focus| once it is more than `max_distance` away. returns how far
+    /// everything already rendered has to move, None if the origin stays.
+    pub fn follow(&mut self, focus: DVec3) -> Option<Vec3> {
+        if (focus - self.origin).length() <= self.max_distance {
+            return None;
+        }
+        let shift = (self.origin - focus).as_vec3();
+        self.origin = focus;
+        Some(shift)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::dvec3;
+
+    use super::*;
+
+    #[test]
+    fn test_placed_curve() {
+        // far out on the map, where f32 world coordinates are only good to a millimetre
+        let (x, z) = (40_000.123, 30_000.456);
+        let pts = [
+            dvec3(x, 0.0, z),
+            dvec3(x + 50.0, 0.0, z + 50.0),
+            dvec3(x + 100.0, 0.0, z),
+        ];
+        let curve = PlacedCurve::quadratic(pts).unwrap();
+        assert_eq!(curve.start(), pts[0]);
+        assert!((curve.end() - pts[2]).length() < 1e-5);
+        // symmetric, so the middle by arc length is the middle of the Bézier curve
+        let mid = (pts[0] + pts[1] * 2.0 + pts[2]) / 4.0;
+        assert!((curve.position(0.5) - mid).length() < 1e-3);
+        let projection = curve.project(mid + dvec3(0.0, 0.0, 1.0));
+        assert!((projection.length as f64 - curve.length() / 2.0).abs() < 1e-3);
+
+        let mut floating = FloatingOrigin::new(1000.0);
+        assert_eq!(floating.follow(dvec3(10.0, 0.0, 0.0)), None);
+        assert!(floating.follow(mid).is_some());
+        let rendered = curve.relative_to(&floating);
+        assert!(rendered.position(0.5).length() < 1e-3);
+        assert!((floating.to_world(rendered.end()) - pts[2]).length() < 1e-5);
+
+        let bbox = curve.bbox();
+        for t in [0.0, 0.25, 0.5, 0.75, 1.0] {
+            let p = curve.position(t);
+            assert!(bbox.contains(p.as_vec3()), "{:?} not in {:?}", p, bbox);
+        }
+
+        let rebased = curve.rebase(pts[2]);
+        assert!((rebased.start() - pts[0]).length() < 1e-5);
+        assert!((rebased.length() - curve.length()).abs() < 1e-9);
+    }
+}
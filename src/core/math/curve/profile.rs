@@ -0,0 +1,285 @@
+//! vertical alignment, the height of a road along its plan.
+//!
+//! a profile is a chain of straight grades meeting at points of vertical intersection
+//! (PVIs). an inner PVI may be rounded by a parabolic vertical curve centered on it: a crest
+//! where the grade drops, a sag where it rises. heights are looked up by station, the arc
+//! length along the plan curve on the ground.
+
+use bevy::math::{vec3, Vec3};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    line::LineSegment,
+    offset::{fit_hermite, MAX_DEPTH},
+    quadratic::QuadraticBezierCurve,
+    segment::Segment,
+    CurveSegment,
+};
+use crate::core::math::error::{check_finite, GeometryError, Result};
+
+/// a point of vertical intersection
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Pvi {
+    pub station: f32,
+    pub elevation: f32,
+    /// horizontal length of the vertical curve centered here, 0 for a sharp kink
+    pub curve_length: f32,
+}
+
+impl Pvi {
+    pub fn new(station: f32, elevation: f32, curve_length: f32) -> Self {
+        Self {
+            station,
+            elevation,
+            curve_length,
+        }
+    }
+}
+
+/// elevation by station. the grades before the first and after the last PVI go on forever.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerticalProfile {
+    pvis: Vec<Pvi>,
+}
+
+impl VerticalProfile {
+    pub fn flat(elevation: f32) -> Self {
+        Self {
+            pvis: vec![Pvi::new(0.0, elevation, 0.0)],
+        }
+    }
+
+    /// |pvis| sorted by station. fails if a grade is steeper than |max_grade| (rise over
+    /// run), if the ends have vertical curves or if two vertical curves overlap.
+    pub fn new(pvis: Vec<Pvi>, max_grade: f32) -> Result<Self> {
+        let (Some(first), Some(last)) = (pvis.first(), pvis.last()) else {
+            return Err(GeometryError::Degenerate {
+                what: "profile without PVIs",
+                points: vec![],
+            });
+        };
+        if first.curve_length != 0.0 || (pvis.len() > 1 && last.curve_length != 0.0) {
+            return Err(GeometryError::InvalidArgument {
+                name: "curve_length of an end PVI",
+                value: first.curve_length.max(last.curve_length),
+            });
+        }
+        let points = pvis
+            .iter()
+            .map(|pvi| vec3(pvi.station, pvi.elevation, pvi.curve_length))
+            .collect::<Vec<_>>();
+        check_finite("PVI", &points)?;
+        if let Some(pvi) = pvis.iter().find(|pvi| pvi.curve_length < 0.0) {
+            return Err(GeometryError::InvalidArgument {
+                name: "PVI curve_length",
+                value: pvi.curve_length,
+            });
+        }
+        for pair in pvis.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            if a.station >= b.station {
+                return Err(GeometryError::InvalidRange {
+                    start: a.station,
+                    end: b.station,
+                    limit: f32::INFINITY,
+                });
+            }
+            let grade = (b.elevation - a.elevation) / (b.station - a.station);
+            if grade.abs() > max_grade {
+                return Err(GeometryError::GradeTooSteep {
+                    grade,
+                    max: max_grade,
+                });
+            }
+            if a.station + a.curve_length / 2.0 > b.station - b.curve_length / 2.0 {
+                return Err(GeometryError::InvalidRange {
+                    start: a.station + a.curve_length / 2.0,
+                    end: b.station - b.curve_length / 2.0,
+                    limit: b.station - a.station,
+                });
+            }
+        }
+        Ok(Self { pvis })
+    }
+
+    pub fn pvis(&self) -> &[Pvi] {
+        &self.pvis
+    }
+
+    /// the grade of the tangent from PVI |i| to the next one. out of range is the grade of
+    /// the closest tangent.
+    fn tangent_grade(&self, i: usize) -> f32 {
+        if self.pvis.len() < 2 {
+            return 0.0;
+        }
+        let i = i.min(self.pvis.len() - 2);
+        let (a, b) = (self.pvis[i], self.pvis[i + 1]);
+        (b.elevation - a.elevation) / (b.station - a.station)
+    }
+
+    /// the PVI whose vertical curve covers |station|, and the distance from the curve start
+    fn vertical_curve_at(&self, station: f32) -> Option<(usize, f32)> {
+        let i = self.pvis.partition_point(|pvi| pvi.station < station);
+        [i.wrapping_sub(1), i]
+            .into_iter()
+            .filter(|&j| j < self.pvis.len())
+            .find_map(|j| {
+                let pvi = self.pvis[j];
+                let x = station - (pvi.station - pvi.curve_length / 2.0);
+                (pvi.curve_length > 0.0 && x > 0.0 && x < pvi.curve_length).then_some((j, x))
+            })
+    }
+
+    /// the tangent |station| is on, ignoring vertical curves
+    fn tangent_at(&self, station: f32) -> usize {
+        self.pvis
+            .partition_point(|pvi| pvi.station <= station)
+            .saturating_sub(1)
+    }
+
+    pub fn elevation(&self, station: f32) -> f32 {
+        if let Some((j, x)) = self.vertical_curve_at(station) {
+            let pvi = self.pvis[j];
+            let (g1, g2) = (self.tangent_grade(j - 1), self.tangent_grade(j));
+            let start = pvi.elevation - g1 * pvi.curve_length / 2.0;
+            return start + g1 * x + (g2 - g1) / (2.0 * pvi.curve_length) * x * x;
+        }
+        let i = self
+            .tangent_at(station)
+            .min(self.pvis.len().saturating_sub(2));
+        let pvi = self.pvis[i];
+        pvi.elevation + self.tangent_grade(i) * (station - pvi.station)
+    }
+
+    /// rise over run at |station|
+    pub fn grade(&self, station: f32) -> f32 {
+        if let Some((j, x)) = self.vertical_curve_at(station) {
+            let (g1, g2) = (self.tangent_grade(j - 1), self.tangent_grade(j));
+            return g1 + (g2 - g1) * x / self.pvis[j].curve_length;
+        }
+        self.tangent_grade(self.tangent_at(station))
+    }
+
+    /// the steepest grade of the profile
+    pub fn max_grade(&self) -> f32 {
+        (0..self.pvis.len().saturating_sub(1))
+            .map(|i| self.tangent_grade(i).abs())
+            .fold(0.0, f32::max)
+    }
+
+    /// stations strictly between |start| and |end| where the curvature of the profile jumps
+    fn breaks(&self, start: f32, end: f32) -> impl Iterator<Item = f32> + '_ {
+        self.pvis
+            .iter()
+            .flat_map(|pvi| {
+                if pvi.curve_length > 0.0 {
+                    let half = pvi.curve_length / 2.0;
+                    vec![pvi.station - half, pvi.station + half]
+                } else {
+                    vec![pvi.station]
+                }
+            })
+            .filter(move |&s| s > start && s < end)
+    }
+}
+
+/// lay |segment| of a plan curve, starting at station |prefix_len|, on |profile| within
+/// |tolerance| meters. the heights of the segment itself are dropped.
+pub(super) fn drape_segment(
+    segment: &CurveSegment,
+    prefix_len: f32,
+    profile: &VerticalProfile,
+    tolerance: f32,
+) -> Vec<CurveSegment> {
+    let length = segment.length();
+    let mut cuts = vec![0.0];
+    cuts.extend(
+        profile
+            .breaks(prefix_len, prefix_len + length)
+            .map(|s| s - prefix_len),
+    );
+    cuts.push(length);
+
+    // the point and its derivative with respect to the station
+    let at = |station: f32| {
+        let t = segment.t_of_length(station);
+        let p = segment.position(t);
+        let d = segment.tangent(t);
+        let elevation = profile.elevation(prefix_len + station);
+        let grade = profile.grade(prefix_len + station);
+        (
+            vec3(p.x, elevation, p.z),
+            vec3(d.x, 0.0, d.z).normalize_or_zero() + Vec3::Y * grade,
+        )
+    };
+    let mut rets = vec![];
+    for piece in cuts.windows(2) {
+        let (start, end) = (piece[0], piece[1]);
+        match segment {
+            // a straight plan under a parabola is a parabola, exactly a quadratic
+            CurveSegment::Line(_) => {
+                let ((p0, d0), (p2, _)) = (at(start), at(end));
+                let mid = (start + end) / 2.0;
+                if profile.vertical_curve_at(prefix_len + mid).is_some() {
+                    let p1 = p0 + d0 * (mid - start);
+                    rets.push(QuadraticBezierCurve::new([p0, p1, p2]).into());
+                } else {
+                    rets.push(LineSegment::new([p0, p2]).into());
+                }
+            }
+            _ => fit_hermite(&at, start, end, tolerance, MAX_DEPTH, &mut rets),
+        }
+    }
+    rets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vertical_profile() {
+        // up at 5% to a crest at station 100, then down at 5%
+        let pvis = vec![
+            Pvi::new(0.0, 0.0, 0.0),
+            Pvi::new(100.0, 5.0, 40.0),
+            Pvi::new(200.0, 0.0, 0.0),
+        ];
+        assert_eq!(
+            VerticalProfile::new(pvis.clone(), 0.04),
+            Err(GeometryError::GradeTooSteep {
+                grade: 0.05,
+                max: 0.04
+            })
+        );
+        let profile = VerticalProfile::new(pvis, 0.06).unwrap();
+        assert_eq!(profile.max_grade(), 0.05);
+
+        assert_eq!(profile.elevation(-20.0), -1.0);
+        assert_eq!(profile.elevation(50.0), 2.5);
+        assert_eq!(profile.grade(50.0), 0.05);
+        // the crest is half way between the tangents' height at the PVI and the curve ends
+        assert!((profile.elevation(100.0) - 4.5).abs() < 1e-5);
+        assert!(profile.grade(100.0).abs() < 1e-6);
+        assert!((profile.grade(90.0) - 0.025).abs() < 1e-6);
+        // the curve meets the tangents smoothly
+        for station in [80.0, 120.0] {
+            let near = [station - 1e-2, station + 1e-2];
+            let [a, b] = near.map(|s| profile.elevation(s));
+            assert!((a - b).abs() < 2e-3);
+            let [a, b] = near.map(|s| profile.grade(s));
+            assert!((a - b).abs() < 1e-4);
+        }
+        assert_eq!(profile.elevation(150.0), 2.5);
+        assert_eq!(profile.grade(250.0), -0.05);
+
+        let overlapping = vec![
+            Pvi::new(0.0, 0.0, 0.0),
+            Pvi::new(10.0, 0.0, 12.0),
+            Pvi::new(20.0, 1.0, 12.0),
+            Pvi::new(30.0, 0.0, 0.0),
+        ];
+        assert!(VerticalProfile::new(overlapping, 1.0).is_err());
+        assert_eq!(VerticalProfile::flat(3.0).elevation(10.0), 3.0);
+    }
+}
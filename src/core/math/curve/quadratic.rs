@@ -1,8 +1,15 @@
 use bevy::math::Vec3;
+use serde::{Deserialize, Serialize};
 
-use super::Curve;
+use crate::core::math::{
+    aabb::Aabb,
+    error::{check_finite, GeometryError, Result},
+    poly::solve_cubic,
+};
 
-#[derive(Clone, Debug)]
+use super::{segment::Segment, Curve};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct QuadraticBezierCurve {
     pub ctrl_pts: [Vec3; 3],
 }
@@ -11,6 +18,18 @@ impl QuadraticBezierCurve {
         Self { ctrl_pts }
     }
 
+    /// fails on non-finite control points or when they all coincide
+    pub fn try_new(ctrl_pts: [Vec3; 3]) -> Result<Self> {
+        check_finite("quadratic curve", &ctrl_pts)?;
+        if ctrl_pts.iter().all(|&p| p == ctrl_pts[0]) {
+            return Err(GeometryError::Degenerate {
+                what: "quadratic curve",
+                points: ctrl_pts.to_vec(),
+            });
+        }
+        Ok(Self::new(ctrl_pts))
+    }
+
     pub fn start(&self) -> Vec3 {
         self.ctrl_pts[0]
     }
@@ -20,10 +39,7 @@ impl QuadraticBezierCurve {
     }
 
     pub fn to_curve(&self) -> super::Curve {
-        super::Curve {
-            curves: vec![self.clone()],
-            sum_lengths: vec![self.length()],
-        }
+        super::Curve::from_segments(vec![self.clone().into()])
     }
     pub fn to_curve2(&self, min_angle: f32, max_length: f32) -> super::Curve {
         // subdivide the curve into multiple segments
@@ -31,21 +47,14 @@ impl QuadraticBezierCurve {
         let mut curves = Vec::new();
         let (p0, p1, p2) = (self.ctrl_pts[0], self.ctrl_pts[1], self.ctrl_pts[2]);
         if (p1 - p0).length() < 1e-4 || (p1 - p2).length() < 1e-4 {
-            return super::Curve {
-                curves: vec![self.clone()],
-                sum_lengths: vec![self.length()],
-            };
+            return self.to_curve();
         }
         // check the angle between the two tangents
         let v = p1 - p0;
         let u = p2 - p1;
         let angle = v.angle_between(-u);
-        println!("angle: {:?}", angle);
         if angle > min_angle && self.length() < max_length {
-            return super::Curve {
-                curves: vec![self.clone()],
-                sum_lengths: vec![self.length()],
-            };
+            return self.to_curve();
         }
         let left_mid = (p0 + p1) / 2.0;
         let right_mid = (p1 + p2) / 2.0;
@@ -54,51 +63,12 @@ impl QuadraticBezierCurve {
         let right_curve = QuadraticBezierCurve::new([mid, right_mid, p2]);
         curves.append(&mut left_curve.to_curve2(min_angle, max_length).curves);
         curves.append(&mut right_curve.to_curve2(min_angle, max_length).curves);
-        // sum_lengths is prefix sum of the lengths of each curve
-        let sum_lengths: Vec<f32> = curves.iter().map(|c| c.length()).collect();
-        // calculate the prefix sum of the lengths of each curve
-        let sum_lengths = sum_lengths
-            .iter()
-            .scan(0.0, |acc, &x| {
-                *acc += x;
-                Some(*acc)
-            })
-            .collect();
-
-        super::Curve {
-            curves,
-            sum_lengths,
-        }
+        super::Curve::from_segments(curves)
     }
 
-    pub fn distance_to(&self, pt: Vec3) -> f32 {
-        // TODO: optimize this
-        let mut min_dist = f32::INFINITY;
-
-        for i in 0..=1024 {
-            let t = i as f32 / 1024.0;
-            let pos = self.position(t);
-            if (pos - pt).length() < min_dist {
-                min_dist = (pos - pt).length();
-            }
-        }
-        min_dist
-    }
-
-    // split the curve at t
+    // split the curve at the point closest to pt
     pub fn split_at(&self, pt: Vec3) -> (Curve, Curve) {
-        let mut min_dist = f32::INFINITY;
-        let mut t = 0.0;
-        for i in 0..=1024 {
-            let t_ = i as f32 / 1024.0;
-            let pos = self.position(t_);
-            let dist = (pos - pt).length();
-            if dist < min_dist {
-                min_dist = dist;
-                t = t_;
-            }
-        }
-        let (curve1, curve2) = self.split_at_t(t);
+        let (curve1, curve2) = self.split_at_t(self.closest_t(pt));
         (curve1.to_curve(), curve2.to_curve())
     }
 
@@ -117,16 +87,8 @@ impl QuadraticBezierCurve {
 
     // construct parallel curve
     pub fn iter_positions(&self, n: isize) -> impl Iterator<Item = Vec3> + '_ {
-        let mut t = 0.0;
-        let step = 1.0 / n as f32;
-        std::iter::from_fn(move || {
-            if t > 1.0 {
-                return None;
-            }
-            let pos = self.position(t);
-            t += step;
-            Some(pos)
-        })
+        let n = n.max(1);
+        (0..=n).map(move |i| self.position(i as f32 / n as f32))
     }
 
     pub fn position(&self, t: f32) -> Vec3 {
@@ -141,7 +103,28 @@ impl QuadraticBezierCurve {
         one_minus_t_sq * p0 + 2.0 * one_minus_t * t * p1 + t_sq * p2
     }
 
-    // we ignore z axis for now
+    /// the box around the end points and the extremes on each axis, where the derivative
+    /// `2 (1 - t) (p1 - p0) + 2 t (p2 - p1)` is 0
+    pub fn bbox(&self) -> Aabb {
+        let [p0, p1, p2] = self.ctrl_pts;
+        let denom = p0 - 2.0 * p1 + p2;
+        let extremes = (0..3).filter_map(|axis| {
+            if denom[axis].abs() < 1e-9 {
+                return None;
+            }
+            let t = (p0[axis] - p1[axis]) / denom[axis];
+            (t > 0.0 && t < 1.0).then(|| self.position(t))
+        });
+        Aabb::from_points([p0, p2].into_iter().chain(extremes))
+    }
+
+    /// the derivative B'(t)
+    pub fn velocity(&self, t: f32) -> Vec3 {
+        let [p0, p1, p2] = self.ctrl_pts;
+        2.0 * (1.0 - t) * (p1 - p0) + 2.0 * t * (p2 - p1)
+    }
+
+    // arc length in 3D, so slopes count
     pub fn length(&self) -> f32 {
         self.length_of(1.)
     }
@@ -196,32 +179,80 @@ impl QuadraticBezierCurve {
         }
         let cap_c = p_b.length_squared();
 
-        let b = cap_b / ((2.0 * cap_a) + EPS);
-        let c = cap_c / (cap_a + EPS);
-        let u = t + b;
-        let k = c - (b * b);
-        let ret = 0.5
-            * (cap_a).max(0.).sqrt()
-            * ((u * ((u * u) + k).max(0.).sqrt()) - (b * ((b * b) + k).max(0.).sqrt())
-                + (k * (((u + ((u * u) + k).max(0.).sqrt())
-                    / (b + ((b * b) + k).max(0.).sqrt() + EPS))
-                    .abs()
-                    + EPS)
-                    .max(EPS)
-                    .ln()));
-        if ret.is_nan() {
-            println!(
-                "nan!!! p0: {:?}, p1: {:?}, p2: {:?}, t: {:?}",
-                p0, p1, p2, t
-            );
-            if p0.is_nan() || p1.is_nan() || p2.is_nan() || t.is_nan() {
-                panic!("nan!!!");
-            }
-            // TODO: report this error
-            1e-6
-        } else {
-            ret
-        }
+        // in f64, the difference of the two terms cancels badly in f32
+        let (cap_a, cap_b, cap_c) = (cap_a as f64, cap_b as f64, cap_c as f64);
+        let b = cap_b / (2.0 * cap_a);
+        let c = cap_c / cap_a;
+        let u = t as f64 + b;
+        // k >= 0 by Cauchy-Schwarz, 0 when the control points are collinear
+        let k = (c - (b * b)).max(0.);
+        let antiderivative = |u: f64| {
+            let s = (u * u + k).sqrt();
+            u * s + k * (u + s).abs().max(f64::MIN_POSITIVE).ln()
+        };
+        // finite control points can't give NaN here, see `try_new`
+        let ret = (0.5 * cap_a.sqrt() * (antiderivative(u) - antiderivative(b))) as f32;
+        debug_assert!(!ret.is_nan(), "NaN length of {:?} at {}", self, t);
+        ret
+    }
+}
+
+impl Segment for QuadraticBezierCurve {
+    fn position(&self, t: f32) -> Vec3 {
+        QuadraticBezierCurve::position(self, t)
+    }
+
+    fn tangent(&self, t: f32) -> Vec3 {
+        self.velocity(t)
+    }
+
+    fn acceleration(&self, _t: f32) -> Vec3 {
+        let [p0, p1, p2] = self.ctrl_pts;
+        2.0 * (p0 - 2.0 * p1 + p2)
+    }
+
+    fn length_of(&self, t: f32) -> f32 {
+        QuadraticBezierCurve::length_of(self, t)
+    }
+
+    fn split_at_t(&self, t: f32) -> (Self, Self) {
+        QuadraticBezierCurve::split_at_t(self, t)
+    }
+
+    fn reverse(&self) -> Self {
+        let [p0, p1, p2] = self.ctrl_pts;
+        Self::new([p2, p1, p0])
+    }
+
+    fn translate(&self, offset: Vec3) -> Self {
+        Self::new(self.ctrl_pts.map(|p| p + offset))
+    }
+
+    fn bbox(&self) -> Aabb {
+        QuadraticBezierCurve::bbox(self)
+    }
+
+    /// the nearest point satisfies (B(t) - pt) . B'(t) = 0, which is a cubic in t
+    fn closest_t(&self, pt: Vec3) -> f32 {
+        let [p0, p1, p2] = self.ctrl_pts.map(|p| p.as_dvec3());
+        // B(t) - pt = a t^2 + 2b t + c, B'(t) = 2(a t + b)
+        let a = p0 - 2.0 * p1 + p2;
+        let b = p1 - p0;
+        let c = p0 - pt.as_dvec3();
+        solve_cubic(
+            a.dot(a),
+            3.0 * a.dot(b),
+            2.0 * b.dot(b) + a.dot(c),
+            b.dot(c),
+        )
+        .into_iter()
+        .filter(|t| *t > 0.0 && *t < 1.0)
+        .map(|t| t as f32)
+        .chain([0.0, 1.0])
+        .map(|t| (t, (self.position(t) - pt).length_squared()))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(t, _)| t)
+        .unwrap_or(0.0)
     }
 }
 
@@ -239,6 +270,12 @@ mod tests {
         ]);
         let pos = curve.position(0.5);
         assert_eq!(pos, Vec3::new(1.0, 0.5, 0.0));
+        // 0.1 doesn't add up to exactly 1.0, the last point must still be the end
+        assert_eq!(curve.iter_positions(10).count(), 11);
+        assert_eq!(
+            curve.iter_positions(10).last(),
+            Some(Vec3::new(2.0, 0.0, 0.0))
+        );
     }
 
     #[test]
@@ -472,4 +509,31 @@ mod tests {
         let u = curve2.velocity(0.0);
         assert!((v - u).length() < 1e-6, "v: {:?}, u: {:?}", v, u);
     }
+
+    #[test]
+    fn test_project() {
+        let curve = QuadraticBezierCurve::new([
+            vec3(-4., 0., -4.),
+            vec3(0.0, 0.0, 10.0),
+            vec3(2., 0.0, -4.0),
+        ]);
+        for pt in [
+            vec3(0.0, 0.0, 0.0),
+            vec3(-1.0, 2.0, 3.0),
+            vec3(5.0, 0.0, 5.0),
+            vec3(-10.0, 0.0, -10.0),
+        ] {
+            let projection = curve.project(pt);
+            let sampled = (0..=100000)
+                .map(|i| (curve.position(i as f32 / 100000.0) - pt).length())
+                .fold(f32::INFINITY, f32::min);
+            assert!(
+                (projection.distance - sampled).abs() < 1e-4,
+                "projection: {:?}, sampled: {:?}",
+                projection,
+                sampled
+            );
+            assert!((projection.length - curve.length_of(projection.t)).abs() < 1e-6);
+        }
+    }
 }
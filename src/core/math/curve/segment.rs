@@ -0,0 +1,183 @@
+use bevy::math::Vec3;
+
+use crate::core::math::aabb::Aabb;
+
+use super::Projection;
+
+/// the moving frame of a curve at some point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrenetFrame {
+    pub position: Vec3,
+    /// unit tangent, the direction of travel
+    pub tangent: Vec3,
+    /// unit normal, towards the center of curvature. on straight pieces it falls back to
+    /// the right hand side on the ground (the side `offset` moves to with positive `right`)
+    pub normal: Vec3,
+    /// tangent x normal
+    pub binormal: Vec3,
+    /// signed curvature (1 / radius), positive when turning to the right hand side
+    pub curvature: f32,
+}
+
+/// a parametric piece of a [`Curve`](super::Curve). t is in [0, 1].
+pub trait Segment: Sized {
+    fn position(&self, t: f32) -> Vec3;
+
+    /// the derivative of [`Self::position`] with respect to t. normalize it to get the unit
+    /// tangent.
+    fn tangent(&self, t: f32) -> Vec3;
+
+    /// the second derivative of [`Self::position`] with respect to t
+    fn acceleration(&self, t: f32) -> Vec3;
+
+    /// arc length from 0 to t
+    fn length_of(&self, t: f32) -> f32;
+
+    fn split_at_t(&self, t: f32) -> (Self, Self);
+
+    /// the same segment traversed from the end to the start
+    fn reverse(&self) -> Self;
+
+    /// the same segment moved by |offset|
+    fn translate(&self, offset: Vec3) -> Self;
+
+    /// the tightest axis aligned box around the segment
+    fn bbox(&self) -> Aabb;
+
+    fn start(&self) -> Vec3 {
+        self.position(0.0)
+    }
+
+    fn end(&self) -> Vec3 {
+        self.position(1.0)
+    }
+
+    fn length(&self) -> f32 {
+        self.length_of(1.0)
+    }
+
+    fn unit_tangent(&self, t: f32) -> Vec3 {
+        self.tangent(t).normalize_or_zero()
+    }
+
+    /// signed curvature at t, positive when turning to the right hand side (+z when heading
+    /// +x)
+    fn curvature(&self, t: f32) -> f32 {
+        let d1 = self.tangent(t);
+        let d2 = self.acceleration(t);
+        let speed = d1.length();
+        if speed < 1e-9 {
+            return 0.0;
+        }
+        let k = d1.cross(d2).length() / (speed * speed * speed);
+        if d2.cross(d1).y < 0.0 {
+            -k
+        } else {
+            k
+        }
+    }
+
+    fn frenet(&self, t: f32) -> FrenetFrame {
+        let d1 = self.tangent(t);
+        let d2 = self.acceleration(t);
+        let tangent = d1.normalize_or_zero();
+        // the part of the acceleration that bends the curve
+        let bend = d2 - tangent * d2.dot(tangent);
+        let normal = if bend.length() > 1e-6 * d1.length_squared().max(1e-12) {
+            bend.normalize()
+        } else {
+            tangent.cross(Vec3::Y).normalize_or_zero()
+        };
+        FrenetFrame {
+            position: self.position(t),
+            tangent,
+            normal,
+            binormal: tangent.cross(normal),
+            curvature: self.curvature(t),
+        }
+    }
+
+    /// the t where the arc length from the start reaches |length|, by Newton's method
+    fn t_of_length(&self, length: f32) -> f32 {
+        let total = self.length();
+        if total <= 0.0 || length <= 0.0 {
+            return 0.0;
+        }
+        if length >= total {
+            return 1.0;
+        }
+        let (mut low, mut high) = (0.0_f32, 1.0_f32);
+        let mut t = length / total;
+        for _ in 0..16 {
+            let err = self.length_of(t) - length;
+            if err.abs() < 1e-5 * total.max(1.0) {
+                break;
+            }
+            if err > 0.0 {
+                high = t;
+            } else {
+                low = t;
+            }
+            let speed = self.tangent(t).length();
+            t = if speed > 1e-9 { t - err / speed } else { -1.0 };
+            // fall back to bisection when newton leaves the bracket
+            if t <= low || t >= high {
+                t = (low + high) / 2.0;
+            }
+        }
+        t
+    }
+
+    /// t of the point on the segment that is closest to |pt|.
+    ///
+    /// the default samples the segment coarsely and refines every local minimum with
+    /// Gauss-Newton steps on `(B(t) - pt) . B'(t) = 0`.
+    fn closest_t(&self, pt: Vec3) -> f32 {
+        const SAMPLES: usize = 32;
+        let dist_sq = |t: f32| (self.position(t) - pt).length_squared();
+        let samples = (0..=SAMPLES)
+            .map(|i| dist_sq(i as f32 / SAMPLES as f32))
+            .collect::<Vec<f32>>();
+        let mut best = (0.0, samples[0]);
+        for i in 0..=SAMPLES {
+            let is_local_min = (i == 0 || samples[i] <= samples[i - 1])
+                && (i == SAMPLES || samples[i] <= samples[i + 1]);
+            if !is_local_min {
+                continue;
+            }
+            let mut t = i as f32 / SAMPLES as f32;
+            for _ in 0..8 {
+                let d = self.tangent(t);
+                let len_sq = d.length_squared();
+                if len_sq < 1e-12 {
+                    break;
+                }
+                let step = (self.position(t) - pt).dot(d) / len_sq;
+                t = (t - step).clamp(0.0, 1.0);
+                if step.abs() < 1e-7 {
+                    break;
+                }
+            }
+            let dist = dist_sq(t);
+            if dist < best.1 {
+                best = (t, dist);
+            }
+        }
+        best.0
+    }
+
+    fn distance_to(&self, pt: Vec3) -> f32 {
+        (self.position(self.closest_t(pt)) - pt).length()
+    }
+
+    /// project |pt| onto the segment. the index of a single segment is always 0.
+    fn project(&self, pt: Vec3) -> Projection {
+        let t = self.closest_t(pt);
+        Projection {
+            index: 0,
+            t,
+            length: self.length_of(t),
+            distance: (self.position(t) - pt).length(),
+        }
+    }
+}
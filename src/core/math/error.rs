@@ -0,0 +1,45 @@
+//! what can go wrong when building or editing geometry
+
+use bevy::math::Vec3;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum GeometryError {
+    /// the control points don't span a usable shape, e.g. they all coincide
+    #[error("degenerate {what}: {points:?}")]
+    Degenerate {
+        what: &'static str,
+        points: Vec<Vec3>,
+    },
+    #[error("a curve needs at least one segment")]
+    EmptyCurve,
+    #[error("NaN or infinity in {what}")]
+    NaN { what: &'static str },
+    /// a range of arc lengths or parameters that is reversed or outside [0, `limit`]
+    #[error("invalid range {start}..{end} of 0..{limit}")]
+    InvalidRange { start: f32, end: f32, limit: f32 },
+    #[error("{name} is out of range: {value}")]
+    InvalidArgument { name: &'static str, value: f32 },
+    /// two curves to be joined don't meet, or meet at an angle
+    #[error("curves are {gap} apart and meet at {angle} rad")]
+    Discontinuous { gap: f32, angle: f32 },
+    #[error("grade {grade} is steeper than {max}")]
+    GradeTooSteep { grade: f32, max: f32 },
+    /// the turn can't be made wider than `best` but `min_radius` was asked for
+    #[error("can't turn wider than {best}, {min_radius} needed")]
+    TooTight { min_radius: f32, best: f32 },
+    /// the inputs are valid but no such shape exists
+    #[error("no {0} exists")]
+    NoSolution(&'static str),
+}
+
+pub type Result<T, E = GeometryError> = std::result::Result<T, E>;
+
+/// fail with [`GeometryError::NaN`] unless all |points| are finite
+pub(crate) fn check_finite(what: &'static str, points: &[Vec3]) -> Result<()> {
+    if points.iter().all(|p| p.is_finite()) {
+        Ok(())
+    } else {
+        Err(GeometryError::NaN { what })
+    }
+}
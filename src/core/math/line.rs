@@ -1,12 +1,402 @@
-use bevy::math::Vec3;
+//! straight lines, rays and segments.
+//!
+//! a line is given by two points p and q; the point at parameter s is `p + s (q - p)`, so a
+//! segment covers s in [0, 1], a ray s >= 0 and a full line every s. intersections report
+//! the parameter on both sides. `*_xz` versions look from above and ignore y, which is what
+//! roads on the ground plane need.
+
+use bevy::math::{vec3, Vec3};
+
+use super::curve::{line::LineSegment, Curve};
+
+/// points closer than this (in meters) meet
+const EPS: f32 = 1e-4;
+
+/// how far a line reaches past its two points
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extent {
+    Line,
+    /// from p through q and beyond
+    Ray,
+    /// from p to q
+    Segment,
+}
+
+impl Extent {
+    /// the range of the parameter
+    fn range(&self) -> (f32, f32) {
+        match self {
+            Extent::Line => (f32::NEG_INFINITY, f32::INFINITY),
+            Extent::Ray => (0.0, f32::INFINITY),
+            Extent::Segment => (0.0, 1.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Intersection {
+    None,
+    /// a single crossing at |s| on self and |t| on the other line
+    Point {
+        s: f32,
+        t: f32,
+        point: Vec3,
+    },
+    /// collinear and overlapping from s.0 to s.1 on self, which is t.0 to t.1 on the other
+    Overlap {
+        s: (f32, f32),
+        t: (f32, f32),
+    },
+}
 
 pub trait Line {
-    fn intersection(&self, other: &impl Line);
+    /// the point at parameter 0
+    fn p(&self) -> Vec3;
+
+    /// the point at parameter 1
+    fn q(&self) -> Vec3;
+
+    fn extent(&self) -> Extent;
+
+    fn direction(&self) -> Vec3 {
+        self.q() - self.p()
+    }
+
+    fn point_at(&self, s: f32) -> Vec3 {
+        self.p() + self.direction() * s
+    }
+
+    /// the parameter of the point closest to |pt|
+    fn closest_param(&self, pt: Vec3) -> f32 {
+        let d = self.direction();
+        let len_sq = d.length_squared();
+        if len_sq < 1e-12 {
+            return 0.0;
+        }
+        let (low, high) = self.extent().range();
+        ((pt - self.p()).dot(d) / len_sq).clamp(low, high)
+    }
+
+    fn closest_point(&self, pt: Vec3) -> Vec3 {
+        self.point_at(self.closest_param(pt))
+    }
+
+    fn distance_to(&self, pt: Vec3) -> f32 {
+        (self.closest_point(pt) - pt).length()
+    }
+
+    /// parameters (s on self, t on other) of the closest pair of points
+    fn closest_params(&self, other: &impl Line) -> (f32, f32) {
+        closest_params(
+            (self.p(), self.direction(), self.extent()),
+            (other.p(), other.direction(), other.extent()),
+        )
+    }
+
+    /// where the two lines meet in space, within a small tolerance
+    fn intersection(&self, other: &impl Line) -> Intersection {
+        intersection(
+            (self.p(), self.direction(), self.extent()),
+            (other.p(), other.direction(), other.extent()),
+        )
+    }
+
+    /// where the two lines cross seen from above. the points are on self
+    fn intersection_xz(&self, other: &impl Line) -> Intersection {
+        let flat = |v: Vec3| vec3(v.x, 0.0, v.z);
+        match intersection(
+            (flat(self.p()), flat(self.direction()), self.extent()),
+            (flat(other.p()), flat(other.direction()), other.extent()),
+        ) {
+            Intersection::Point { s, t, .. } => Intersection::Point {
+                s,
+                t,
+                point: self.point_at(s),
+            },
+            other => other,
+        }
+    }
+
+    /// crossings with |curve| seen from above, sorted along self.
+    /// return (s on self, t on curve as in [`Curve::position`], point on self)
+    fn intersections_with_curve(&self, curve: &Curve) -> Vec<(f32, f32, Vec3)> {
+        let (d, aabb) = (self.direction(), curve.bbox().expand(1.0));
+        // clip to the box of the curve, the line might be endless
+        let (mut low, mut high) = self.extent().range();
+        for (p, d, min, max) in [
+            (self.p().x, d.x, aabb.min.x, aabb.max.x),
+            (self.p().z, d.z, aabb.min.z, aabb.max.z),
+        ] {
+            if d.abs() < 1e-12 {
+                if p < min || p > max {
+                    return vec![];
+                }
+                continue;
+            }
+            let (a, b) = ((min - p) / d, (max - p) / d);
+            low = low.max(a.min(b));
+            high = high.min(a.max(b));
+        }
+        if low > high || !low.is_finite() || !high.is_finite() {
+            return vec![];
+        }
+        let chord = LineSegment::new([self.point_at(low), self.point_at(high)]).to_curve();
+        let mut hits = chord
+            .intersections(curve)
+            .into_iter()
+            .map(|(u, t, _)| {
+                let s = low + (high - low) * u;
+                (s, t, self.point_at(s))
+            })
+            .collect::<Vec<_>>();
+        hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+        hits
+    }
 }
 
+/// a line through two points
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct LineByPQ {
-    p0: Vec3,
-    p1: Vec3,
-    q0: Vec3,
-    q1: Vec3,
+    p: Vec3,
+    q: Vec3,
+    extent: Extent,
+}
+
+impl LineByPQ {
+    pub fn new(p: Vec3, q: Vec3, extent: Extent) -> Self {
+        Self { p, q, extent }
+    }
+
+    pub fn line(p: Vec3, q: Vec3) -> Self {
+        Self::new(p, q, Extent::Line)
+    }
+
+    /// the ray from |origin| heading to |direction|
+    pub fn ray(origin: Vec3, direction: Vec3) -> Self {
+        Self::new(origin, origin + direction, Extent::Ray)
+    }
+
+    pub fn segment(p: Vec3, q: Vec3) -> Self {
+        Self::new(p, q, Extent::Segment)
+    }
+}
+
+impl Line for LineByPQ {
+    fn p(&self) -> Vec3 {
+        self.p
+    }
+
+    fn q(&self) -> Vec3 {
+        self.q
+    }
+
+    fn extent(&self) -> Extent {
+        self.extent
+    }
+}
+
+impl Line for LineSegment {
+    fn p(&self) -> Vec3 {
+        self.ctrl_pts[0]
+    }
+
+    fn q(&self) -> Vec3 {
+        self.ctrl_pts[1]
+    }
+
+    fn extent(&self) -> Extent {
+        Extent::Segment
+    }
+}
+
+/// a line as (point at 0, direction, extent)
+type Param = (Vec3, Vec3, Extent);
+
+fn closest_params((p1, d1, e1): Param, (p2, d2, e2): Param) -> (f32, f32) {
+    let ((lo1, hi1), (lo2, hi2)) = (e1.range(), e2.range());
+    let r = p1 - p2;
+    let (a, b, e) = (d1.length_squared(), d1.dot(d2), d2.length_squared());
+    let (c, f) = (d1.dot(r), d2.dot(r));
+    // t of the point on 2 closest to the point s on 1, and the other way round
+    let t_of = |s: f32| {
+        if e < 1e-12 {
+            0.0
+        } else {
+            ((b * s + f) / e).clamp(lo2, hi2)
+        }
+    };
+    let s_of = |t: f32| {
+        if a < 1e-12 {
+            0.0
+        } else {
+            ((b * t - c) / a).clamp(lo1, hi1)
+        }
+    };
+    let denom = a * e - b * b;
+    let s = if denom > 1e-9 * a * e {
+        ((b * f - c * e) / denom).clamp(lo1, hi1)
+    } else {
+        // parallel, any s works; take the one closest to 0 in range
+        0.0_f32.clamp(lo1, hi1)
+    };
+    let t = t_of(s);
+    let s = s_of(t);
+    (s, t_of(s))
+}
+
+fn intersection(l1: Param, l2: Param) -> Intersection {
+    let ((p1, d1, e1), (p2, d2, e2)) = (l1, l2);
+    let (a, e) = (d1.length_squared(), d2.length_squared());
+    let parallel = d1.cross(d2).length_squared() <= 1e-10 * a * e;
+    if !parallel || a < 1e-12 || e < 1e-12 {
+        let (s, t) = closest_params(l1, l2);
+        let (x, y) = (p1 + d1 * s, p2 + d2 * t);
+        if (x - y).length() > EPS {
+            return Intersection::None;
+        }
+        return Intersection::Point {
+            s,
+            t,
+            point: (x + y) / 2.0,
+        };
+    }
+    // parallel, they meet only when collinear
+    let offset = (p2 - p1) - d1 * (p2 - p1).dot(d1) / a;
+    if offset.length() > EPS {
+        return Intersection::None;
+    }
+    // the range of 2 in the parameter of 1
+    let to_s = |t: f32| (p2 + d2 * t - p1).dot(d1) / a;
+    let (lo2, hi2) = e2.range();
+    let ends = [lo2, hi2].map(|t| {
+        if t.is_finite() {
+            to_s(t)
+        } else {
+            // an endless end goes where the direction points
+            t.signum() * d1.dot(d2).signum() * f32::INFINITY
+        }
+    });
+    let (lo1, hi1) = e1.range();
+    let low = ends[0].min(ends[1]).max(lo1);
+    let high = ends[0].max(ends[1]).min(hi1);
+    let tolerance = EPS / a.sqrt();
+    if low > high + tolerance {
+        return Intersection::None;
+    }
+    let to_t = |s: f32| (p1 + d1 * s - p2).dot(d2) / e;
+    if high - low <= tolerance {
+        return Intersection::Point {
+            s: low,
+            t: to_t(low),
+            point: p1 + d1 * low,
+        };
+    }
+    Intersection::Overlap {
+        s: (low, high),
+        t: (to_t(low), to_t(high)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::vec3;
+
+    use super::*;
+    use crate::core::math::curve::arc::ArcSegment;
+
+    #[test]
+    fn test_intersection() {
+        let a = LineByPQ::segment(vec3(0.0, 0.0, 0.0), vec3(4.0, 0.0, 0.0));
+        let b = LineByPQ::segment(vec3(1.0, 0.0, -1.0), vec3(1.0, 0.0, 3.0));
+        assert_eq!(
+            a.intersection(&b),
+            Intersection::Point {
+                s: 0.25,
+                t: 0.25,
+                point: vec3(1.0, 0.0, 0.0)
+            }
+        );
+        // misses as segments, crosses as a ray
+        let c = LineByPQ::segment(vec3(5.0, 0.0, -1.0), vec3(5.0, 0.0, 1.0));
+        assert_eq!(a.intersection(&c), Intersection::None);
+        let ray = LineByPQ::ray(vec3(0.0, 0.0, 0.0), vec3(2.0, 0.0, 0.0));
+        assert!(matches!(
+            ray.intersection(&c),
+            Intersection::Point { s, .. } if (s - 2.5).abs() < 1e-6
+        ));
+        // skew in space, crossing from above
+        let high = LineByPQ::segment(vec3(1.0, 2.0, -1.0), vec3(1.0, 2.0, 3.0));
+        assert_eq!(a.intersection(&high), Intersection::None);
+        assert!(matches!(
+            a.intersection_xz(&high),
+            Intersection::Point { point, .. } if point == vec3(1.0, 0.0, 0.0)
+        ));
+        // parallel and collinear
+        let parallel = LineByPQ::segment(vec3(0.0, 0.0, 1.0), vec3(4.0, 0.0, 1.0));
+        assert_eq!(a.intersection(&parallel), Intersection::None);
+        let collinear = LineByPQ::segment(vec3(6.0, 0.0, 0.0), vec3(2.0, 0.0, 0.0));
+        assert_eq!(
+            a.intersection(&collinear),
+            Intersection::Overlap {
+                s: (0.5, 1.0),
+                t: (1.0, 0.5)
+            }
+        );
+        let touching = LineByPQ::segment(vec3(4.0, 0.0, 0.0), vec3(6.0, 0.0, 0.0));
+        assert!(matches!(
+            a.intersection(&touching),
+            Intersection::Point { s, t, .. } if s == 1.0 && t == 0.0
+        ));
+        let line = LineByPQ::line(vec3(-1.0, 0.0, 0.0), vec3(-2.0, 0.0, 0.0));
+        assert!(matches!(
+            line.intersection(&a),
+            Intersection::Overlap { t: (t0, t1), .. } if t0.min(t1) == 0.0 && t0.max(t1) == 1.0
+        ));
+    }
+
+    #[test]
+    fn test_closest() {
+        let a = LineByPQ::segment(vec3(0.0, 0.0, 0.0), vec3(4.0, 0.0, 0.0));
+        assert_eq!(a.closest_point(vec3(-2.0, 0.0, 1.0)), vec3(0.0, 0.0, 0.0));
+        assert_eq!(a.distance_to(vec3(2.0, 3.0, 0.0)), 3.0);
+        let line = LineByPQ::line(vec3(0.0, 0.0, 0.0), vec3(4.0, 0.0, 0.0));
+        assert_eq!(line.closest_param(vec3(-2.0, 0.0, 1.0)), -0.5);
+
+        let b = LineByPQ::segment(vec3(5.0, 1.0, 2.0), vec3(6.0, 1.0, -2.0));
+        let (s, t) = a.closest_params(&b);
+        assert_eq!(s, 1.0);
+        // |(5 + t, 1, 2 - 4t) - (4, 0, 0)| is smallest at t = 7 / 17
+        assert!((t - 7.0 / 17.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_intersections_with_curve() {
+        // a half circle of radius 2 around the origin, from (2, 0, 0) through (0, 0, 2)
+        let arc = ArcSegment::new(
+            Vec3::ZERO,
+            vec3(2.0, 0.0, 0.0),
+            -Vec3::Y,
+            std::f32::consts::PI,
+        );
+        let curve = arc.to_curve();
+        let line = LineByPQ::line(vec3(0.0, 0.0, 1.0), vec3(1.0, 0.0, 1.0));
+        let hits = line.intersections_with_curve(&curve);
+        assert_eq!(hits.len(), 2);
+        let x = 3.0_f32.sqrt();
+        assert!(
+            (hits[0].2 - vec3(-x, 0.0, 1.0)).length() < 1e-3,
+            "{:?}",
+            hits
+        );
+        assert!(
+            (hits[1].2 - vec3(x, 0.0, 1.0)).length() < 1e-3,
+            "{:?}",
+            hits
+        );
+        assert!((hits[0].1 - 5.0 / 6.0).abs() < 1e-3);
+        let ray = LineByPQ::ray(vec3(0.0, 0.0, 1.0), vec3(1.0, 0.0, 0.0));
+        assert_eq!(ray.intersections_with_curve(&curve).len(), 1);
+        let far = LineByPQ::segment(vec3(5.0, 0.0, 1.0), vec3(6.0, 0.0, 1.0));
+        assert!(far.intersections_with_curve(&curve).is_empty());
+    }
 }
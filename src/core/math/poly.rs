@@ -0,0 +1,107 @@
+//! real roots of low degree polynomials
+
+/// real roots of `a x^3 + b x^2 + c x + d = 0`. falls back to lower degrees when the leading
+/// coefficients vanish. roots are not sorted and a double root may appear twice.
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    const EPS: f64 = 1e-12;
+    let scale = a.abs().max(b.abs()).max(c.abs()).max(d.abs());
+    if scale < EPS {
+        return vec![];
+    }
+    if a.abs() < EPS * scale {
+        return solve_quadratic(b, c, d);
+    }
+    // depressed cubic t^3 + p t + q = 0 with x = t - b / 3a
+    let (b, c, d) = (b / a, c / a, d / a);
+    let shift = b / 3.0;
+    let p = c - b * b / 3.0;
+    let q = 2.0 * b * b * b / 27.0 - b * c / 3.0 + d;
+    let disc = q * q / 4.0 + p * p * p / 27.0;
+    let roots = if disc > EPS {
+        // one real root
+        let sqrt = disc.sqrt();
+        vec![(-q / 2.0 + sqrt).cbrt() + (-q / 2.0 - sqrt).cbrt()]
+    } else if disc < -EPS {
+        // three real roots, trigonometric method
+        let r = (-p / 3.0).sqrt();
+        let phi = (-q / (2.0 * r * r * r)).clamp(-1.0, 1.0).acos();
+        (0..3)
+            .map(|k| 2.0 * r * ((phi - 2.0 * std::f64::consts::PI * k as f64) / 3.0).cos())
+            .collect()
+    } else {
+        let u = (-q / 2.0).cbrt();
+        vec![2.0 * u, -u]
+    };
+    roots
+        .into_iter()
+        .map(|t| t - shift)
+        // one newton step to clean up the cancellation error
+        .map(|x| {
+            let f = ((x + b) * x + c) * x + d;
+            let df = (3.0 * x + 2.0 * b) * x + c;
+            if df.abs() > EPS {
+                x - f / df
+            } else {
+                x
+            }
+        })
+        .collect()
+}
+
+/// real roots of `a x^2 + b x + c = 0`
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    const EPS: f64 = 1e-12;
+    let scale = a.abs().max(b.abs()).max(c.abs());
+    if scale < EPS {
+        return vec![];
+    }
+    if a.abs() < EPS * scale {
+        if b.abs() < EPS * scale {
+            return vec![];
+        }
+        return vec![-c / b];
+    }
+    let disc = b * b - 4.0 * a * c;
+    if disc < 0.0 {
+        return vec![];
+    }
+    // avoid cancellation, see numerical recipes 5.6
+    let q = -0.5 * (b + b.signum() * disc.sqrt());
+    if q.abs() < EPS * scale {
+        return vec![0.0];
+    }
+    vec![q / a, c / q]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(mut roots: Vec<f64>, expected: &[f64]) {
+        roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        roots.dedup_by(|a, b| (*a - *b).abs() < 1e-6);
+        assert_eq!(roots.len(), expected.len(), "roots: {:?}", roots);
+        for (r, e) in roots.iter().zip(expected) {
+            assert!(
+                (r - e).abs() < 1e-6,
+                "roots: {:?}, expected: {:?}",
+                roots,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_solve_cubic() {
+        // (x - 1)(x - 2)(x + 3)
+        assert_roots(solve_cubic(1.0, 0.0, -7.0, 6.0), &[-3.0, 1.0, 2.0]);
+        // (x - 2)(x^2 + 1)
+        assert_roots(solve_cubic(2.0, -4.0, 2.0, -4.0), &[2.0]);
+        // (x - 1)^2 (x + 2)
+        assert_roots(solve_cubic(1.0, 0.0, -3.0, 2.0), &[-2.0, 1.0]);
+        // degenerate to quadratic and linear
+        assert_roots(solve_cubic(0.0, 1.0, -3.0, 2.0), &[1.0, 2.0]);
+        assert_roots(solve_cubic(0.0, 0.0, 2.0, -1.0), &[0.5]);
+        assert_roots(solve_quadratic(1.0, 0.0, 1.0), &[]);
+    }
+}
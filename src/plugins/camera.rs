@@ -6,20 +6,33 @@ use bevy::{
     input::mouse::{MouseMotion, MouseWheel},
     math::{Mat3, Vec3},
 };
+use cage::core::math::curve::placed::FloatingOrigin;
 
 
 pub struct CageCameraPlugin;
 impl Plugin for CageCameraPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(ScrollWheelMomentum(0.0))
+            .insert_resource(RenderOrigin(FloatingOrigin::new(MAX_RENDER_DISTANCE)))
             .add_systems(Startup, (setup_ground, spawn_camera))
-            .add_systems(Update, (pan_orbit_camera, update_momentum));
+            .add_systems(
+                Update,
+                (pan_orbit_camera, update_momentum, follow_camera_focus).chain(),
+            );
     }
 }
 
 #[derive(Resource)]
 struct ScrollWheelMomentum(f32);
 
+/// how far the camera focus may get from the render origin before the origin moves to it
+const MAX_RENDER_DISTANCE: f64 = 1000.0;
+
+/// the world position rendered at (0, 0, 0). everything with a [`Transform`] is placed
+/// relative to it, and it follows the camera so that what is on screen stays precise.
+#[derive(Resource, Deref, DerefMut)]
+pub struct RenderOrigin(pub FloatingOrigin);
+
 #[derive(Component)]
 pub struct Ground;
 
@@ -135,6 +148,26 @@ fn pan_orbit_camera(
     ev_motion.clear();
 }
 
+/// move the render origin to the camera focus once it is far away, and everything rendered
+/// along with it
+fn follow_camera_focus(
+    mut origin: ResMut<RenderOrigin>,
+    mut cameras: Query<&mut PanOrbitCamera>,
+    mut roots: Query<&mut Transform, Without<Parent>>,
+) {
+    let Ok(mut camera) = cameras.get_single_mut() else {
+        return;
+    };
+    let focus = origin.to_world(camera.focus);
+    let Some(shift) = origin.follow(focus) else {
+        return;
+    };
+    camera.focus += shift;
+    for mut transform in roots.iter_mut() {
+        transform.translation += shift;
+    }
+}
+
 fn setup_ground(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -1,4 +1,5 @@
 pub mod road;
+pub mod road_mesh;
 pub mod car;
 pub mod path;
 pub mod path_op;
@@ -1,9 +1,19 @@
 use std::collections::VecDeque;
 
 use bevy::{
-    math::Vec3, pbr::PbrBundle, prelude::*, render::mesh::Mesh, time::Time, utils::HashSet,
+    math::{DVec3, Vec3},
+    pbr::PbrBundle,
+    prelude::*,
+    render::mesh::Mesh,
+    time::Time,
+    utils::HashSet,
 };
-use cage::core::math::curve::quadratic::QuadraticBezierCurve;
+use cage::core::math::{
+    collider::obb::Obb,
+    curve::{placed::PlacedCurve, quadratic::QuadraticBezierCurve},
+};
+
+use crate::plugins::camera::RenderOrigin;
 
 use super::{
     path::{link_next, Path},
@@ -16,14 +26,27 @@ use super::{
 #[derive(Component, Debug)]
 pub struct Car {
     length: f32,
+    width: f32,
     speed: f32,
-    last_position: Vec3,
+    last_position: DVec3,
     acceleration: f32,
     acc_max: f32,
     // entity of (pathSlice, Option<lock group>)
     pub path_slices: VecDeque<Entity>,
 }
 
+impl Car {
+    /// the ground the car covers when placed at |transform|
+    pub fn footprint(&self, transform: &Transform) -> Obb {
+        Obb::new(
+            transform.translation,
+            *transform.forward(),
+            self.length,
+            self.width,
+        )
+    }
+}
+
 #[derive(Bundle)]
 pub struct CarBundle {
     car: Car,
@@ -268,6 +291,7 @@ pub fn car_move(
     mut index: ResMut<PathLockIndex>,
     mut car_query: Query<(Entity, &mut Car, &mut Transform)>,
     mut locked_path_slices_query: Query<&mut PathSlicesLocked>,
+    origin: Res<RenderOrigin>,
 
     time: Res<Time>,
 ) {
@@ -322,8 +346,8 @@ pub fn car_move(
             continue;
         }
         index.upsert_locks(&car_e, locked_path_slices.locks.iter().cloned());
-        let translate = Transform::from_translation(car.last_position);
-        let rotation = translate.looking_at(position, Vec3::Y);
+        let translate = Transform::from_translation(origin.to_render(car.last_position));
+        let rotation = translate.looking_at(origin.to_render(position), Vec3::Y);
         car.last_position = position;
         // align the car's front/center/bottom with the path
         let shift = Transform::from_translation(Vec3::new(0.0, 0.5, -0.8));
@@ -331,6 +355,16 @@ pub fn car_move(
     }
 }
 
+/// the outline of every car's footprint
+pub fn show_debug_car(cars: Query<(&Car, &Transform)>, mut gizmos: Gizmos) {
+    for (car, transform) in cars.iter() {
+        let corners = car.footprint(transform).corners();
+        for i in 0..corners.len() {
+            gizmos.line(corners[i], corners[(i + 1) % corners.len()], Color::YELLOW);
+        }
+    }
+}
+
 pub fn test_setup_car_and_path(
     mut commands: Commands,
     mut lock_index: ResMut<PathLockIndex>,
@@ -343,6 +377,7 @@ pub fn test_setup_car_and_path(
         Vec3::new(0.0, 0.0, 6.0),
     ])
     .to_curve();
+    let curve_a = PlacedCurve::new(DVec3::ZERO, curve_a);
 
     let path_a = commands
         .spawn(Path {
@@ -358,6 +393,7 @@ pub fn test_setup_car_and_path(
         Vec3::new(0.0, 0.0, 6.0),
     ])
     .to_curve();
+    let curve_c = PlacedCurve::new(DVec3::ZERO, curve_c);
     let path_c = commands
         .spawn(Path {
             curve: curve_c.clone(),
@@ -371,6 +407,7 @@ pub fn test_setup_car_and_path(
         Vec3::new(0.0, 0.0, -929.0),
     ])
     .to_curve();
+    let curve_e = PlacedCurve::new(DVec3::ZERO, curve_e);
     let path_e = commands
         .spawn(Path {
             curve: curve_e.clone(),
@@ -454,11 +491,12 @@ pub fn test_setup_car_and_path(
                 },
                 car: Car {
                     length: 2.1,
+                    width: 1.0,
                     speed: 0.0,
                     acceleration: 0.0,
                     acc_max: 123.9 + rand::random::<f32>() * 5.0,
                     path_slices: car_a_slices.clone(),
-                    last_position: Vec3::ONE * 999.0,
+                    last_position: DVec3::ONE * 999.0,
                 },
                 intent: PathIntent::empty(),
                 locks: PathSlicesLocked::empty(),
@@ -482,11 +520,12 @@ pub fn test_setup_car_and_path(
                 },
                 car: Car {
                     length: 2.1,
+                    width: 1.0,
                     speed: 0.,
                     acceleration: 0.0,
                     acc_max: 123.9 + rand::random::<f32>() * 5.0,
                     path_slices: car_b_slices.clone(),
-                    last_position: Vec3::ONE * 999.,
+                    last_position: DVec3::ONE * 999.,
                 },
                 intent: PathIntent::empty(),
                 locks: PathSlicesLocked::empty(),
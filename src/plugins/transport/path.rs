@@ -1,18 +1,22 @@
-use bevy::prelude::*;
-use cage::core::math::curve::{quadratic::QuadraticBezierCurve, Curve};
+use bevy::{math::DVec3, prelude::*};
+use cage::core::math::curve::{placed::PlacedCurve, quadratic::QuadraticBezierCurve};
 
 use super::path_op::{schedule_intents, PathLockIndex};
+use crate::plugins::camera::RenderOrigin;
+
+/// how far debug lines may stray from the curves they trace, in meters
+pub const DEBUG_TOLERANCE: f32 = 0.01;
 
 #[derive(Component, Debug, Clone)]
 pub struct Path {
-    pub curve: Curve,
+    pub curve: PlacedCurve,
     // left Path entity
     pub left: Option<Entity>,
     // right Path entity
     pub right: Option<Entity>,
 }
 impl Path {
-    pub fn new(curve: Curve) -> Self {
+    pub fn new(curve: PlacedCurve) -> Self {
         Self {
             curve,
             left: None,
@@ -20,7 +24,7 @@ impl Path {
         }
     }
     pub fn length(&self) -> f32 {
-        self.curve.length()
+        self.curve.local().length()
     }
 }
 
@@ -46,22 +50,25 @@ impl Default for Path {
     fn default() -> Self {
         Self {
             // no default
-            curve: QuadraticBezierCurve::new([Vec3::ZERO, Vec3::ZERO, Vec3::ZERO]).to_curve(),
+            curve: PlacedCurve::new(
+                DVec3::ZERO,
+                QuadraticBezierCurve::new([Vec3::ZERO, Vec3::ZERO, Vec3::ZERO]).to_curve(),
+            ),
             left: None,
             right: None,
         }
     }
 }
 
-pub fn show_debug_path(paths: Query<&Path>, mut gizmos: Gizmos) {
+pub fn show_debug_path(paths: Query<&Path>, origin: Res<RenderOrigin>, mut gizmos: Gizmos) {
     for path in paths.iter() {
-        path.curve
-            .iter_positions(64)
-            .collect::<Vec<Vec3>>()
+        let curve = path.curve.relative_to(&origin);
+        curve
+            .to_polyline(DEBUG_TOLERANCE)
             .windows(2)
             .for_each(|p| gizmos.line(p[0], p[1], Color::WHITE));
-        let src = path.curve.position(0.0);
-        let dst = path.curve.position(1.0);
+        let src = curve.start();
+        let dst = curve.end();
         gizmos.circle(src, Direction3d::Y, 0.05, Color::GREEN);
         gizmos.circle(dst + Vec3::Y * 0.05, Direction3d::Y, 0.05, Color::RED);
     }
@@ -100,6 +107,7 @@ pub fn link_next(
                     this_from: next_from,
                     prev_until: this_until,
                 })
+                .set_parent(dst_path_e)
                 .id();
             Some((next_ret, prev))
         })
@@ -110,8 +118,48 @@ pub struct PathPlugin;
 impl Plugin for PathPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(PathLockIndex::new())
-            .add_systems(Update, schedule_intents);
+            .add_systems(Update, (schedule_intents, show_debug_path));
         // app.add_startup_system(test_setup_path.system())
-        //     .add_system(show_debug_path.system());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{dvec3, vec3};
+    use cage::core::math::curve::{line::LineSegment, placed::FloatingOrigin, Curve};
+
+    use super::*;
+
+    #[test]
+    fn test_debug_polyline() {
+        // a lane on a bend far out on the map, as the debug lines trace it around the
+        // render origin
+        let center = QuadraticBezierCurve::new([
+            vec3(0.0, 0.0, 0.0),
+            vec3(20.0, 0.0, 0.0),
+            vec3(20.0, 2.0, 20.0),
+        ])
+        .to_curve();
+        let origin = dvec3(2_000_000.3, 0.0, -2_000_000.7);
+        let path = Path::new(PlacedCurve::new(origin, center.offset(0.75, 0.0)));
+        let mut floating = FloatingOrigin::new(1000.0);
+        floating.follow(origin + dvec3(10.0, 0.0, 10.0));
+        let curve = path.curve.relative_to(&floating);
+        let polyline = curve.to_polyline(DEBUG_TOLERANCE);
+        assert_eq!(polyline[0], curve.start());
+        assert_eq!(*polyline.last().unwrap(), curve.end());
+        assert!(polyline.len() > 2);
+
+        let traced = Curve::from_segments(
+            polyline
+                .windows(2)
+                .map(|w| LineSegment::new([w[0], w[1]]).into())
+                .collect(),
+        );
+        for i in 0..=200 {
+            let pt = floating.to_render(path.curve.position(i as f64 / 200.0));
+            let dist = traced.distance_to(pt);
+            assert!(dist <= DEBUG_TOLERANCE + 1e-4, "{}", dist);
+        }
     }
 }
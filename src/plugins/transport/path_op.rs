@@ -1,10 +1,11 @@
 use std::{borrow::BorrowMut, collections::VecDeque};
 
 use bevy::{
+    math::DVec3,
     prelude::*,
     utils::{HashMap, HashSet},
 };
-use cage::core::math::curve::Curve;
+use cage::core::math::curve::placed::PlacedCurve;
 use rand::prelude::*;
 
 /// PathSlice is a slice of a path
@@ -14,11 +15,11 @@ pub struct PathSlice {
     pub path_e: Entity,
     pub start: f32,
     pub end: f32,
-    pub parent_curve: Curve,
+    pub parent_curve: PlacedCurve,
 }
 
 impl PathSlice {
-    pub fn new(path_e: Entity, start: f32, end: f32, parent_curve: Curve) -> Self {
+    pub fn new(path_e: Entity, start: f32, end: f32, parent_curve: PlacedCurve) -> Self {
         Self {
             path_e,
             start,
@@ -28,7 +29,7 @@ impl PathSlice {
     }
 
     pub fn length(&self) -> f32 {
-        self.parent_curve.length() * (self.end - self.start)
+        self.parent_curve.local().length() * (self.end - self.start)
     }
 
     pub fn parent_t_of_length(&self, length: f32) -> f32 {
@@ -39,9 +40,9 @@ impl PathSlice {
         length / (self.length())
     }
 
-    pub fn position(&self, progress: f32) -> Vec3 {
+    pub fn position(&self, progress: f32) -> DVec3 {
         self.parent_curve
-            .position(self.start + progress * (self.end - self.start))
+            .position((self.start + progress * (self.end - self.start)) as f64)
     }
 }
 
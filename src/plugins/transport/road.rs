@@ -1,136 +1,635 @@
 use anyhow::Result;
 use anyhow::anyhow;
 use bevy::{
+    ecs::system::SystemParam,
+    math::DVec3,
     prelude::*,
     utils::{HashMap, HashSet},
 };
-use cage::core::math::curve::{quadratic::QuadraticBezierCurve, Curve};
-use std::{cmp::Ordering, vec};
+use cage::core::math::{
+    aabb::Aabb,
+    bvh::Bvh,
+    collider::{curve_pair::CurvePair, hull::ConvexHull, Collider, Penetration},
+    curve::{
+        line::LineSegment,
+        placed::{FloatingOrigin, PlacedCurve},
+        profile::{Pvi, VerticalProfile},
+        quadratic::QuadraticBezierCurve,
+        Curve, Projection,
+    },
+    error::{self as geometry, GeometryError},
+};
+use std::vec;
+
+use crate::plugins::{
+    camera::{Ground, RenderOrigin},
+    transport::path::Path,
+};
+
+use super::{
+    path::{self, PathNext, PathPrev, DEBUG_TOLERANCE},
+    road_mesh::{setup_road_material, update_road_meshes},
+};
+
+/// how far apart in height two roads must be to cross without meeting
+pub const CLEARANCE: f32 = 3.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpanKind {
+    Bridge,
+    Tunnel,
+}
+
+/// a stretch of road carried by a structure, by arc length along the center
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Span {
+    pub kind: SpanKind,
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Span {
+    pub fn covers(&self, length: f32) -> bool {
+        self.start <= length && length <= self.end
+    }
+}
+
+/// the parts of |spans| within [start, end], measured from |start|
+fn slice_spans(spans: &[Span], start: f32, end: f32) -> Vec<Span> {
+    spans
+        .iter()
+        .filter(|span| span.end > start && span.start < end)
+        .map(|span| Span {
+            kind: span.kind,
+            start: span.start.max(start) - start,
+            end: span.end.min(end) - start,
+        })
+        .collect()
+}
+
+/// sort |spans| and join the overlapping ones of the same kind
+fn merge_spans(mut spans: Vec<Span>) -> Vec<Span> {
+    spans.sort_by(|a, b| a.start.total_cmp(&b.start));
+    let mut ret: Vec<Span> = vec![];
+    for span in spans {
+        match ret.last_mut() {
+            Some(last) if last.kind == span.kind && span.start <= last.end => {
+                last.end = last.end.max(span.end);
+            }
+            _ => ret.push(span),
+        }
+    }
+    ret
+}
+
+/// how a road is divided across: its lanes side by side, with a shoulder on both edges.
+///
+/// a two-way road drives on the right: forward lanes, along the center curve, are on its
+/// right hand side and backward lanes on the left.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lanes {
+    /// lanes in each direction
+    pub count: usize,
+    pub lane_width: f32,
+    pub shoulder: f32,
+    pub two_way: bool,
+}
+
+impl Lanes {
+    pub fn one_way(count: usize, lane_width: f32, shoulder: f32) -> Self {
+        Self {
+            count,
+            lane_width,
+            shoulder,
+            two_way: false,
+        }
+    }
+
+    pub fn two_way(count: usize, lane_width: f32, shoulder: f32) -> Self {
+        Self {
+            two_way: true,
+            ..Self::one_way(count, lane_width, shoulder)
+        }
+    }
 
-use crate::plugins::{camera::Ground, transport::path::Path};
+    /// from edge to edge, shoulders included
+    pub fn width(&self) -> f32 {
+        let directions = if self.two_way { 2. } else { 1. };
+        directions * self.count as f32 * self.lane_width + 2. * self.shoulder
+    }
 
-use super::path::{self, PathNext, PathPrev};
+    /// how far right of the center each lane of one direction runs, from its leftmost lane.
+    /// backward lanes use the same offsets from the reversed center.
+    pub fn offsets(&self) -> Vec<f32> {
+        let left = if self.two_way {
+            0.
+        } else {
+            -(self.count as f32) * self.lane_width / 2.
+        };
+        (0..self.count)
+            .map(|i| left + (i as f32 + 0.5) * self.lane_width)
+            .collect()
+    }
+}
 
 #[derive(Component, Clone, Debug)]
 pub struct Road {
-    pub center: Curve,
-    pub width: f32,
+    pub center: PlacedCurve,
+    pub lanes: Lanes,
     /// m/s
     pub speed_max: f32,
     pub travel_time_avg: f32,
+    /// bridges and tunnels, sorted along the road
+    pub spans: Vec<Span>,
 }
 
 impl Road {
     pub fn length(&self) -> f32 {
-        self.center.length()
+        self.center.local().length()
+    }
+
+    pub fn width(&self) -> f32 {
+        self.lanes.width()
     }
     // m/s
     pub fn avg_speed(&self) -> f32 {
         self.length() / self.travel_time_avg
     }
 
-    pub fn intersects(&self, rhs: &Road) -> Option<Vec3> {
+    /// the box around the road and its width
+    pub fn bbox(&self) -> Aabb {
+        self.center.bbox().expand(self.width() / 2.)
+    }
+
+    /// the ground covered by the road, around the origin of its center
+    pub fn footprint(&self) -> CurvePair {
+        CurvePair::from_center(self.center.local(), self.width())
+    }
+
+    /// the same road with its center around |origin|, to be measured against roads there
+    pub fn rebase(&self, origin: DVec3) -> Self {
+        Self {
+            center: self.center.rebase(origin),
+            ..self.clone()
+        }
+    }
+
+    /// the bridge or tunnel the road is on at |length|
+    pub fn span_at(&self, length: f32) -> Option<&Span> {
+        self.spans.iter().find(|span| span.covers(length))
+    }
+
+    /// whether the roads pass over each other at |t| on self and |rhs_t| on |rhs| instead
+    /// of meeting: either one is on a structure there or their heights are far enough apart
+    fn is_grade_separated(&self, t: f32, rhs: &Road, rhs_t: f32) -> bool {
+        let gap = self.center.position(t as f64).y - rhs.center.position(rhs_t as f64).y;
+        gap.abs() >= CLEARANCE as f64
+            || self.span_at(t * self.length()).is_some()
+            || rhs.span_at(rhs_t * rhs.length()).is_some()
+    }
+
+    /// where the centers meet, sorted along self. they are intersected around the origin of
+    /// self, so roads far out on the map meet as exactly as near it.
+    fn intersections(&self, rhs: &Road) -> Vec<(f32, f32, DVec3)> {
+        let rhs_center = rhs.center.rebase(self.center.origin());
         self.center
-            .iter_positions(1024)
-            .filter_map(|p| {
-                let dist = rhs.center.distance_to(p);
-                if dist < self.width || dist < rhs.width {
-                    Some((dist, p))
+            .local()
+            .intersections(rhs_center.local())
+            .into_iter()
+            .map(|(t, rhs_t, pt)| (t, rhs_t, self.center.to_world(pt)))
+            .collect()
+    }
+
+    /// crossings with |rhs| where one road passes over the other, sorted along self
+    pub fn grade_separations(&self, rhs: &Road) -> Vec<(f32, f32, DVec3)> {
+        self.intersections(rhs)
+            .into_iter()
+            .filter(|(t, rhs_t, _)| self.is_grade_separated(*t, rhs, *rhs_t))
+            .collect()
+    }
+
+    /// carry the road over or under |rhs| wherever their heights differ by [`CLEARANCE`]
+    /// and no span is there yet: a bridge if self is the upper one, a tunnel otherwise
+    pub fn add_spans_over(&mut self, rhs: &Road) {
+        let (len, reach) = (self.length(), (self.width() + rhs.width()) / 2.);
+        let mut spans = self.spans.clone();
+        for (t, rhs_t, _) in self.grade_separations(rhs) {
+            let s = t * len;
+            if self.span_at(s).is_some() {
+                continue;
+            }
+            let above = self.center.position(t as f64).y > rhs.center.position(rhs_t as f64).y;
+            spans.push(Span {
+                kind: if above {
+                    SpanKind::Bridge
                 } else {
-                    None
-                }
-            })
-            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Less))
-            .map(|(_, p)| p)
+                    SpanKind::Tunnel
+                },
+                start: (s - reach).max(0.),
+                end: (s + reach).min(len),
+            });
+        }
+        self.spans = merge_spans(spans);
+    }
+
+    pub fn intersects(&self, rhs: &Road) -> Option<DVec3> {
+        self.crossings(rhs).first().map(|(_, _, pt)| *pt)
+    }
+
+    /// crossings with |rhs| that leave room for a junction, sorted along self.
+    ///
+    /// a junction trims half a width of road on both sides of the crossing, so crossings
+    /// too close to either road's ends or to another crossing are dropped. so are grade
+    /// separated ones, which need no junction.
+    pub fn crossings(&self, rhs: &Road) -> Vec<(f32, f32, DVec3)> {
+        let (len, rhs_len) = (self.length(), rhs.length());
+        let mut ret: Vec<(f32, f32, DVec3)> = vec![];
+        for (t, rhs_t, pt) in self.intersections(rhs) {
+            if self.is_grade_separated(t, rhs, rhs_t) {
+                continue;
+            }
+            let (s, rhs_s) = (t * len, rhs_t * rhs_len);
+            let room = s > self.width() / 2.
+                && len - s > self.width() / 2.
+                && rhs_s > rhs.width() / 2.
+                && rhs_len - rhs_s > rhs.width() / 2.;
+            let apart = ret.iter().all(|(t_, rhs_t_, _)| {
+                (t_ * len - s).abs() > self.width()
+                    && (rhs_t_ * rhs_len - rhs_s).abs() > rhs.width()
+            });
+            if room && apart {
+                ret.push((t, rhs_t, pt));
+            }
+        }
+        ret
     }
 }
 
 #[derive(Component, Clone)]
 pub struct Junction {
-    pub center: Vec3,
+    pub center: DVec3,
+    /// width of the widest road meeting here
+    pub width: f32,
+    /// hull of the connector paths and the square around the center, relative to the center
+    pub footprint: ConvexHull,
+}
+
+impl Junction {
+    /// how far the junction reaches from its center
+    pub fn radius(&self) -> f32 {
+        self.footprint
+            .points()
+            .iter()
+            .map(|p| p.length())
+            .fold(0., f32::max)
+    }
+
+    /// the box around the junction, as [`PlacedCurve::bbox`]
+    pub fn bbox(&self) -> Aabb {
+        self.footprint.bbox().placed_at(self.center)
+    }
+
+    /// the footprint around |origin| instead of the center
+    pub fn footprint_at(&self, origin: DVec3) -> ConvexHull {
+        self.footprint.translate((self.center - origin).as_vec3())
+    }
+
+    /// where along |road| it runs through the junction, None if it passes by, over or
+    /// under it
+    pub fn entry(&self, road: &Road) -> Option<f32> {
+        let length = road.center.project(self.center).length;
+        let pt = (road.center.position_at_length(length as f64) - self.center).as_vec3();
+        (pt.y.abs() < CLEARANCE && self.footprint.contains(pt)).then_some(length)
+    }
+
+    /// the stretch of |road| within [`Self::radius`] of the center, by arc length, if it
+    /// runs through the junction
+    pub fn hole(&self, road: &Road) -> Option<(f32, f32)> {
+        let (s, r) = (self.entry(road)?, self.radius());
+        Some(((s - r).max(0.), (s + r).min(road.length())))
+    }
 }
 
-#[derive(Resource, Debug)]
+/// how many roads and junctions may be added or removed before the index is rebuilt
+const MAX_STALE: usize = 16;
+
+#[derive(Resource, Debug, Default)]
 /// road index is used to query near or collided roads.
+///
+/// it looks from above, like the colliders: boxes and distances ignore heights. the boxes
+/// live in a [`Bvh`], which is rebuilt only once enough has changed. until then new boxes are
+/// searched one by one and removed ones are filtered out.
 pub struct RoadIndex {
-    pub roads: HashSet<Entity>,
-    pub junctions: HashSet<Entity>,
+    /// center of every road, to measure how far away it is
+    roads: HashMap<Entity, PlacedCurve>,
+    junctions: HashSet<Entity>,
+    bvh: Bvh<CollisionTarget>,
+    /// added since the last rebuild, not in |bvh|
+    added: Vec<(Aabb, CollisionTarget)>,
+    /// removed since the last rebuild, still in |bvh|
+    removed: HashSet<Entity>,
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum CollisionTarget {
     Road(Entity),
     Junction(Entity),
 }
 
+impl CollisionTarget {
+    pub fn entity(&self) -> Entity {
+        match self {
+            CollisionTarget::Road(e) | CollisionTarget::Junction(e) => *e,
+        }
+    }
+
+    pub fn road(&self) -> Option<Entity> {
+        match self {
+            CollisionTarget::Road(e) => Some(*e),
+            CollisionTarget::Junction(_) => None,
+        }
+    }
+
+    pub fn junction(&self) -> Option<Entity> {
+        match self {
+            CollisionTarget::Junction(e) => Some(*e),
+            CollisionTarget::Road(_) => None,
+        }
+    }
+}
+
+/// |aabb| seen from above: flattened onto y = 0
+fn ground_box(aabb: &Aabb) -> Aabb {
+    Aabb::new(
+        aabb.min * Vec3::new(1., 0., 1.),
+        aabb.max * Vec3::new(1., 0., 1.),
+    )
+}
+
+fn ground(p: Vec3) -> Vec3 {
+    Vec3::new(p.x, 0., p.z)
+}
+
 impl RoadIndex {
     pub fn new() -> Self {
-        Self {
-            roads: HashSet::new(),
-            junctions: HashSet::new(),
-        }
+        Self::default()
     }
-    pub fn add_road(&mut self, e: Entity) {
-        self.roads.insert(e);
+
+    pub fn add_road(&mut self, e: Entity, road: &Road) {
+        self.roads.insert(e, road.center.clone());
+        self.insert(road.bbox(), CollisionTarget::Road(e));
     }
 
-    pub fn add_junction(&mut self, e: Entity) {
+    pub fn add_junction(&mut self, e: Entity, junction: &Junction) {
         self.junctions.insert(e);
+        self.insert(junction.bbox(), CollisionTarget::Junction(e));
+    }
+
+    /// roads and junctions whose box overlaps |aabb|, candidates to collide with whatever
+    /// |aabb| bounds
+    pub fn collisions(&self, aabb: &Aabb) -> Vec<CollisionTarget> {
+        let aabb = ground_box(aabb);
+        self.query(self.bvh.query_aabb(&aabb), |other| other.intersects(&aabb))
+    }
+
+    /// roads and junctions whose box is within |radius| of |pt|
+    pub fn within_radius(&self, pt: DVec3, radius: f32) -> Vec<CollisionTarget> {
+        let pt = ground(pt.as_vec3());
+        self.query(self.bvh.query_point(pt, radius), |aabb| {
+            aabb.distance_to(pt) <= radius
+        })
+    }
+
+    /// the road whose center comes closest to |pt|, if any is within |max_distance|, and
+    /// where on the center that is
+    pub fn nearest_road(&self, pt: DVec3, max_distance: f32) -> Option<(Entity, Projection)> {
+        self.within_radius(pt, max_distance)
+            .into_iter()
+            .filter_map(|target| target.road())
+            .filter_map(|e| {
+                let center = self.roads.get(&e)?;
+                let projection = center.project(pt);
+                let offset = center.position_at_length(projection.length as f64) - pt;
+                let distance = ground(offset.as_vec3()).length();
+                (distance <= max_distance).then_some((distance, e, projection))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, e, projection)| (e, projection))
     }
 
-    pub fn collisions(
+    fn remove(&mut self, e: Entity) {
+        let known = self.roads.remove(&e).is_some() | self.junctions.remove(&e);
+        if !known {
+            return;
+        }
+        let before = self.added.len();
+        self.added.retain(|(_, target)| target.entity() != e);
+        if self.added.len() == before {
+            self.removed.insert(e);
+        }
+        self.rebuild_if_stale();
+    }
+
+    fn insert(&mut self, aabb: Aabb, target: CollisionTarget) {
+        self.added.push((ground_box(&aabb), target));
+        self.rebuild_if_stale();
+    }
+
+    /// |hits| from the tree that are still there, and the boxes added since that pass |accept|
+    fn query(
         &self,
-        _px: f32,
-        _py: f32,
-        _qx: f32,
-        _qy: f32,
-    ) -> impl Iterator<Item = CollisionTarget> + '_ {
-        self.roads
-            .iter()
-            .map(|r| CollisionTarget::Road(*r))
-            .chain(self.junctions.iter().map(|r| CollisionTarget::Junction(*r)))
+        hits: Vec<&CollisionTarget>,
+        accept: impl Fn(&Aabb) -> bool,
+    ) -> Vec<CollisionTarget> {
+        hits.into_iter()
+            .filter(|target| !self.removed.contains(&target.entity()))
+            .copied()
+            .chain(
+                self.added
+                    .iter()
+                    .filter(|(aabb, _)| accept(aabb))
+                    .map(|(_, target)| *target),
+            )
+            .collect()
     }
 
-    fn remove(&mut self, road: Entity) {
-        self.roads.remove(&road);
-        self.junctions.remove(&road);
+    fn rebuild_if_stale(&mut self) {
+        if self.added.len() + self.removed.len() <= MAX_STALE.max(self.bvh.len() / 4) {
+            return;
+        }
+        let items = self
+            .bvh
+            .iter()
+            .filter(|(_, target)| !self.removed.contains(&target.entity()))
+            .cloned()
+            .chain(self.added.drain(..))
+            .collect();
+        self.bvh = Bvh::new(items);
+        self.removed.clear();
     }
 }
 
+/// what an end of a new road is snapped to in the road tool
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Snap {
+    /// the start (`at_end` false) or end of a road, which the new road continues
+    RoadEnd {
+        road: Entity,
+        at_end: bool,
+    },
+    /// a point along a road, split there into a T-junction
+    OnRoad {
+        road: Entity,
+        at: DVec3,
+    },
+    Junction(Entity),
+}
+
 #[derive(Event, Clone, Debug)]
 pub struct BuildRoad {
-    pub center: Curve,
-    pub width: f32,
+    pub center: PlacedCurve,
+    pub lanes: Lanes,
     pub speed_max: f32,
+    /// stretches to build as bridges or tunnels. more are added where the road passes over
+    /// or under another one.
+    pub spans: Vec<Span>,
+    /// what the start and the end of the road were snapped to
+    pub start: Option<Snap>,
+    pub end: Option<Snap>,
+}
+
+impl BuildRoad {
+    pub fn width(&self) -> f32 {
+        self.lanes.width()
+    }
 }
 
+/// a lane to spawn: the paths it is linked from, its own path and the paths it is linked to
+pub type Lane = (Vec<Entity>, Path, Vec<Entity>);
+
 #[derive(Clone, Debug)]
 pub struct RoadBlueprint {
     pub event: BuildRoad,
-    /// paths: form, owned, next
-    pub paths: Vec<(Option<Entity>, Path, Option<Entity>)>,
+    /// paths: form, owned, next. one per forward lane, from the leftmost lane
+    pub paths: Vec<Lane>,
+    /// the same for the backward lanes of a two-way road, which run from the end of the
+    /// road to its start
+    pub backward: Vec<Lane>,
+}
+
+/// a path along |center| for every lane of one direction, not linked to anything yet
+fn lane_paths(center: &PlacedCurve, lanes: &Lanes) -> Vec<Lane> {
+    lanes
+        .offsets()
+        .into_iter()
+        .map(|offset| {
+            let curve = if offset == 0. {
+                center.clone()
+            } else {
+                PlacedCurve::new(center.origin(), center.local().offset(offset, 0.))
+            };
+            (vec![], Path::new(curve), vec![])
+        })
+        .collect()
 }
 
 impl RoadBlueprint {
+    /// a road with a path along each of its lanes, not linked to anything yet
+    pub fn new(event: BuildRoad) -> Self {
+        let paths = lane_paths(&event.center, &event.lanes);
+        let backward = if event.lanes.two_way {
+            let reversed = PlacedCurve::new(event.center.origin(), event.center.local().reverse());
+            lane_paths(&reversed, &event.lanes)
+        } else {
+            vec![]
+        };
+        Self {
+            event,
+            paths,
+            backward,
+        }
+    }
+
     pub fn to_road(&self) -> Road {
         Road {
             center: self.event.center.clone(),
-            width: self.event.width,
-            speed_max: 2.,
+            lanes: self.event.lanes,
+            speed_max: self.event.speed_max,
             travel_time_avg: 1.,
+            spans: self.event.spans.clone(),
         }
     }
 }
 
 pub struct JunctionBluePrint {
-    center: Vec3,
+    center: DVec3,
+    /// width of the widest road meeting here
+    width: f32,
     connections: Vec<(Option<Entity>, Path, Option<Entity>)>,
 }
 impl JunctionBluePrint {
-    pub fn new(center: Vec3) -> Self {
+    pub fn new(center: DVec3, width: f32) -> Self {
         JunctionBluePrint {
             center,
+            width,
             connections: Vec::new(),
         }
     }
+
+    /// the ground under the connector paths, |width| wide, relative to the center
+    fn footprint(&self) -> ConvexHull {
+        let half = self.width / 2.;
+        let corners = [Vec3::X, Vec3::Z, Vec3::NEG_X, Vec3::NEG_Z].map(|d| d * half);
+        let center = self.center;
+        let sides = self.connections.iter().flat_map(move |(_, path, _)| {
+            (0..=8).flat_map(move |i| {
+                let t = i as f64 / 8.;
+                let p = (path.curve.position(t) - center).as_vec3();
+                let v = path.curve.velocity(t);
+                let right = Vec3::new(-v.z, 0., v.x) * half;
+                [p + right, p - right]
+            })
+        });
+        ConvexHull::from_points(corners.into_iter().chain(sides))
+    }
+}
+
+/// cut the lanes of a road at |at| and hand the pieces to the road before (|road_a_bp|)
+/// and after (|road_b_bp|) the junction there, |width| apart. a forward lane keeps where it
+/// comes from on road a and where it goes on road b, a backward lane the other way round.
+fn split_lanes(
+    paths: Vec<Lane>,
+    backward: Vec<Lane>,
+    at: DVec3,
+    width: f32,
+    road_a_bp: &mut RoadBlueprint,
+    road_b_bp: &mut RoadBlueprint,
+) -> Result<()> {
+    // the pieces stay around the origin of the lane they are cut from
+    let place = |path: &Path, curve: Curve| Path::new(PlacedCurve::new(path.curve.origin(), curve));
+    for (from_e, path, next_e) in paths {
+        let (curve_a, curve_b) = path.curve.local().split_at(path.curve.to_local(at));
+        let curve_a = curve_a.trim_end(width / 2.)?;
+        let curve_b = curve_b.trim_start(width / 2.)?;
+        road_a_bp
+            .paths
+            .push((from_e, place(&path, curve_a), vec![]));
+        road_b_bp
+            .paths
+            .push((vec![], place(&path, curve_b), next_e));
+    }
+    for (from_e, path, next_e) in backward {
+        let (curve_b, curve_a) = path.curve.local().split_at(path.curve.to_local(at));
+        let curve_b = curve_b.trim_end(width / 2.)?;
+        let curve_a = curve_a.trim_start(width / 2.)?;
+        road_b_bp
+            .backward
+            .push((from_e, place(&path, curve_b), vec![]));
+        road_a_bp
+            .backward
+            .push((vec![], place(&path, curve_a), next_e));
+    }
+    Ok(())
 }
 
 /// split road into two road segment.
@@ -138,142 +637,83 @@ impl JunctionBluePrint {
 /// note that path hasn't spawn since blueprint isn't constructed yet.
 fn split_road(
     bp: RoadBlueprint,
-    at: Vec3,
+    at: DVec3,
 ) -> Result<(RoadBlueprint, JunctionBluePrint, RoadBlueprint)> {
-    let curve = bp.event.center;
-    let (curve_a, curve_b) = curve.split_at(at);
+    let width = bp.event.width();
+    let (origin, curve) = (bp.event.center.origin(), bp.event.center.local());
+    let (curve_a, curve_b) = curve.split_at(bp.event.center.to_local(at));
+    let cut = curve_a.length();
+    let spans_a = slice_spans(&bp.event.spans, 0.0, cut - width / 2.);
+    let spans_b = slice_spans(&bp.event.spans, cut + width / 2., curve.length());
 
-    let curve_a = curve_a.slice_by_length(0.0, curve_a.length() - bp.event.width / 2.)?;
-    let curve_b = curve_b.slice_by_length(bp.event.width / 2., curve_b.length())?;
-    let junction_bp = JunctionBluePrint::new(curve_a.end());
+    let curve_a = PlacedCurve::new(origin, curve_a.trim_end(width / 2.)?);
+    let curve_b = PlacedCurve::new(origin, curve_b.trim_start(width / 2.)?);
+    let junction_bp = JunctionBluePrint::new(curve_a.end(), width);
     let mut road_a_bp = RoadBlueprint {
         event: BuildRoad {
             center: curve_a,
-            width: bp.event.width,
+            lanes: bp.event.lanes,
             speed_max: bp.event.speed_max,
+            spans: spans_a,
+            start: None,
+            end: None,
         },
         paths: vec![],
+        backward: vec![],
     };
     let mut road_b_bp = RoadBlueprint {
         event: BuildRoad {
             center: curve_b,
-            width: bp.event.width,
+            lanes: bp.event.lanes,
             speed_max: bp.event.speed_max,
+            spans: spans_b,
+            start: None,
+            end: None,
         },
         paths: vec![],
+        backward: vec![],
     };
 
-    for (from_e, path, next_e) in bp.paths {
-        let (curve_a, curve_b) = path.curve.split_at(at);
-        let curve_a = curve_a.slice_by_length(0.0, curve_a.length() - bp.event.width / 2.)?;
-        let curve_b = curve_b.slice_by_length(bp.event.width / 2., curve_b.length())?;
-
-        road_a_bp.paths.push((
-            from_e,
-            Path {
-                curve: curve_a,
-                left: None,
-                right: None,
-                ..default()
-            },
-            None,
-        ));
-        road_b_bp.paths.push((
-            None,
-            Path {
-                curve: curve_b,
-                left: None,
-                right: None,
-                ..default()
-            },
-            next_e,
-        ));
-    }
+    split_lanes(
+        bp.paths,
+        bp.backward,
+        at,
+        width,
+        &mut road_a_bp,
+        &mut road_b_bp,
+    )?;
     Ok((road_a_bp, junction_bp, road_b_bp))
 }
 
+/// [`split_road`], around |junction_bp| instead of a new junction
 fn split_road_with_existing_junction(
     bp: RoadBlueprint,
-    mut junction_bp: JunctionBluePrint,
-    at: Vec3,
+    junction_bp: JunctionBluePrint,
+    at: DVec3,
 ) -> Result<(RoadBlueprint, JunctionBluePrint, RoadBlueprint)> {
-    let curve = bp.event.center;
-    let (curve_a, curve_b) = curve.split_at(at);
-
-    let curve_a = curve_a.slice_by_length(0.0, curve_a.length() - bp.event.width / 2.)?;
-    let curve_b = curve_b.slice_by_length(bp.event.width / 2., curve_b.length())?;
-
-    let mut road_a_bp = RoadBlueprint {
-        event: BuildRoad {
-            center: curve_a,
-            width: bp.event.width,
-            speed_max: bp.event.speed_max,
-        },
-        paths: vec![],
-    };
-    let mut road_b_bp = RoadBlueprint {
-        event: BuildRoad {
-            center: curve_b,
-            width: bp.event.width,
-            speed_max: bp.event.speed_max,
-        },
-        paths: vec![],
-    };
-
-    for (from_e, path, next_e) in bp.paths {
-        let (curve_a, curve_b) = path.curve.split_at(at);
-        let curve_a = curve_a.slice_by_length(0.0, curve_a.length() - bp.event.width / 2.)?;
-        let curve_b = curve_b.slice_by_length(bp.event.width / 2., curve_b.length())?;
-        road_a_bp.paths.push((
-            from_e,
-            Path {
-                curve: curve_a,
-                left: None,
-                right: None,
-                ..default()
-            },
-            None,
-        ));
-        road_b_bp.paths.push((
-            None,
-            Path {
-                curve: curve_b,
-                left: None,
-                right: None,
-                ..default()
-            },
-            next_e,
-        ));
-    }
+    let (road_a_bp, _, road_b_bp) = split_road(bp, at)?;
     Ok((road_a_bp, junction_bp, road_b_bp))
 }
 
-/// p, v is path_a's entering position and direction
-/// q, u is path_b's outgoing position and direction
-fn connect_road_in_junction(
-    mut commands: &mut Commands,
-    path_a_e: Entity,
-    path_b_e: Entity,
-    junction_e: Entity,
-    p: Vec3,
-    v: Vec3,
-    q: Vec3,
-    u: Vec3,
-) -> Result<()> {
-    let curve = Curve::form_two_velocity(p, v, q, u)?;
-    commands.get_entity(junction_e).map_or(Err(anyhow!("entity not found")), |mut j_ec| {
-        j_ec.with_children(|parent| {
-            parent.spawn(Path {
-                curve,
-                left: None,
-                right: None,
-                ..default()
-            });
-        });
-        Ok(())
-    })
+/// the tightest turn a connector inside a junction may take
+const MIN_TURN_RADIUS: f32 = 0.5;
+
+/// a tangent-continuous connector through the junction that turns no tighter than
+/// [`MIN_TURN_RADIUS`]. it is laid out on the ground and then raised on an even grade
+/// from |p| to |q|.
+fn connector_curve(p: Vec3, v: Vec3, q: Vec3, u: Vec3) -> Result<Curve> {
+    let ground = |a: Vec3| Vec3::new(a.x, 0., a.z);
+    let plan = Curve::from_tangents(ground(p), v, ground(q), u, MIN_TURN_RADIUS)?;
+    let profile = VerticalProfile::new(
+        vec![Pvi::new(0., p.y, 0.), Pvi::new(plan.length(), q.y, 0.)],
+        f32::INFINITY,
+    )?;
+    Ok(plan.with_profile(&profile))
 }
 
+/// connectors from every incoming path to every outgoing path of a junction, keyed by
+/// (incoming group, path, outgoing group, path). group k of both is the same arm, the lanes
+/// into and out of the junction on one road piece, and nothing turns back onto its own arm.
 fn spawn_junction_full_connections(
     incoming_groups: Vec<Vec<Path>>,
     outgoing_groups: Vec<Vec<Path>>,
@@ -281,20 +721,20 @@ fn spawn_junction_full_connections(
     let mut ret = HashMap::<(usize, usize, usize, usize), Path>::new();
     for (i, ips) in incoming_groups.iter().enumerate() {
         for (o, ops) in outgoing_groups.iter().enumerate() {
+            if i == o {
+                continue;
+            }
             for (i2, ip) in ips.iter().enumerate() {
                 for (o2, op) in ops.iter().enumerate() {
-                    ret.insert(
-                        (i, i2, o, o2),
-                        Path {
-                            curve: Curve::form_two_velocity(
-                                ip.curve.end(),
-                                ip.curve.velocity(1.0),
-                                op.curve.start(),
-                                op.curve.velocity(0.0),
-                            )?,
-                            ..default()
-                        },
-                    );
+                    // laid out around where it starts, the lanes may be around other origins
+                    let origin = ip.curve.end();
+                    let curve = connector_curve(
+                        Vec3::ZERO,
+                        ip.curve.velocity(1.0),
+                        (op.curve.start() - origin).as_vec3(),
+                        op.curve.velocity(0.0),
+                    )?;
+                    ret.insert((i, i2, o, o2), Path::new(PlacedCurve::new(origin, curve)));
                 }
             }
         }
@@ -302,346 +742,1287 @@ fn spawn_junction_full_connections(
     Ok(ret)
 }
 
-/// this function despawn old entities and spawn new entities replacing collision roads with
-/// new roads with junctions.
+/// split |road| and the roads it |crossed| at every crossing, and spawn the pieces with a
+/// junction at each crossing. the new road is split once, across all the roads it crosses.
+/// the old roads are left for the caller to despawn.
 fn spawn_split_collision_roads(
-    mut commands: &mut Commands,
-    roads: Vec<RoadBlueprint>,
-    mut road_index: &mut ResMut<RoadIndex>,
+    commands: &mut Commands,
+    road_index: &mut RoadIndex,
+    road: RoadBlueprint,
+    crossed: Vec<RoadBlueprint>,
 ) -> Result<()> {
-    let mut new_roads: Vec<RoadBlueprint> = vec![];
-    for road in roads.into_iter() {
-        let mut rm_idx = HashSet::<usize>::new();
-        for (j, road_other) in new_roads.iter().enumerate() {
-            if (road.event.center.start() - road_other.event.center.start()).length() < 1e-6
-                && (road.event.center.end() - road_other.event.center.end()).length() < 1e-6
-            {
-                continue;
-            }
-            if let Some(pt) = road.to_road().intersects(&road_other.to_road()) {
-                rm_idx.insert(j);
+    let new_road = road.to_road();
+    // (along |road|, along the crossed road, point, crossed road), sorted along |road|, so the
+    // k-th crossing sits between road pieces k and k + 1
+    let mut crossings = crossed
+        .iter()
+        .enumerate()
+        .flat_map(|(j, other)| {
+            new_road
+                .crossings(&other.to_road())
+                .into_iter()
+                .map(move |(t, other_t, pt)| (t, other_t, pt, j))
+        })
+        .collect::<Vec<_>>();
+    crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+    if crossings
+        .windows(2)
+        .any(|c| (c[1].0 - c[0].0) * new_road.length() <= new_road.width())
+    {
+        return Err(anyhow!("the road crosses roads too close together"));
+    }
 
-                let (road_a, junction, road_b) = split_road(road.clone(), pt)?;
-                let (road_c, junction, road_d) =
-                    split_road_with_existing_junction(road_other.clone(), junction, pt)?;
-                println!(
-                    "new roads\n \t{:?}\n\t{:?}\n\t{:?}\n\t{:?}\n",
-                    road_a, road_b, road_c, road_d
-                );
-                let (road_a_e, paths_a_e) = spawn_road(&mut commands, &mut road_index, &road_a);
-                let (road_b_e, paths_b_e) = spawn_road(&mut commands, &mut road_index, &road_b);
-                let (road_c_e, paths_c_e) = spawn_road(&mut commands, &mut road_index, &road_c);
-                let (road_d_e, paths_d_e) = spawn_road(&mut commands, &mut road_index, &road_d);
-                let get_second = |e: &(Option<Entity>, Path, Option<Entity>)| e.1.clone();
-                let rg1 = [
-                    road_a.paths.iter().map(get_second).collect::<Vec<Path>>(),
-                    road_c.paths.iter().map(get_second).collect::<Vec<Path>>(),
-                ]
-                .to_vec();
-                let rg2 = [
-                    road_b.paths.iter().map(get_second).collect::<Vec<Path>>(),
-                    road_d.paths.iter().map(get_second).collect::<Vec<Path>>(),
-                ]
-                .to_vec();
-
-                // generate paths to connect paths_a to b, d
-                // generate paths to connect paths_c to b, d
-                let ret = spawn_junction_full_connections(rg1, rg2)?;
-                let i_roads = [road_a_e, road_c_e];
-                let o_roads = [road_b_e, road_d_e];
-                let i_paths = [paths_a_e, paths_c_e];
-                let o_paths = [paths_b_e, paths_d_e];
-                let junction_e = spawn_junction(
-                    commands,
-                    road_index,
-                    JunctionBluePrint {
-                        center: pt,
-                        connections: ret
-                            .into_iter()
-                            .map(|((ir, ip, or, op), path)| {
-                                (Some(i_paths[ir][ip]), path, Some(o_paths[or][op]))
-                            })
-                            .collect::<Vec<(Option<Entity>, Path, Option<Entity>)>>(),
-                    },
-                );
-            }
-        }
-        if rm_idx.len() == 0 {
-            new_roads.push(road);
+    let mut pieces_a = vec![];
+    let mut rest = road.clone();
+    for (_, _, pt, _) in crossings.iter() {
+        let (road_a, _, road_b) = split_road(rest, *pt)?;
+        pieces_a.push(road_a);
+        rest = road_b;
+    }
+    pieces_a.push(rest);
+
+    // each crossed road is split at its own crossings in its own order
+    let mut rank_b = vec![0; crossings.len()];
+    let mut pieces_b = vec![];
+    for (j, other) in crossed.iter().enumerate() {
+        let mut order = (0..crossings.len())
+            .filter(|k| crossings[*k].3 == j)
+            .collect::<Vec<usize>>();
+        order.sort_by(|x, y| crossings[*x].1.total_cmp(&crossings[*y].1));
+        let mut pieces = vec![];
+        let mut rest = other.clone();
+        for (rank, k) in order.iter().enumerate() {
+            let pt = crossings[*k].2;
+            let (road_c, _, road_d) = split_road_with_existing_junction(
+                rest,
+                JunctionBluePrint::new(pt, other.event.width()),
+                pt,
+            )?;
+            rank_b[*k] = rank;
+            pieces.push(road_c);
+            rest = road_d;
         }
+        pieces.push(rest);
+        pieces_b.push(pieces);
+    }
+
+    let get_second = |e: &Lane| e.1.clone();
+    let paths_of = |lanes: &Vec<Lane>| lanes.iter().map(get_second).collect::<Vec<Path>>();
+    // every connector is laid out before anything is spawned, so a crossing too tight
+    // to turn through leaves the network as it was
+    let mut junctions = vec![];
+    for (k, (_, _, pt, j)) in crossings.iter().enumerate() {
+        let (a_in, b_in) = (k, rank_b[k]);
+        // the four arms: each road before and after the junction. forward lanes
+        // come in on the piece before and leave on the one after, backward lanes
+        // the other way round
+        let (a0, a1) = (&pieces_a[a_in], &pieces_a[a_in + 1]);
+        let (b0, b1) = (&pieces_b[*j][b_in], &pieces_b[*j][b_in + 1]);
+        let rg1: Vec<Vec<Path>> = vec![
+            paths_of(&a0.paths),
+            paths_of(&a1.backward),
+            paths_of(&b0.paths),
+            paths_of(&b1.backward),
+        ];
+        let rg2: Vec<Vec<Path>> = vec![
+            paths_of(&a0.backward),
+            paths_of(&a1.paths),
+            paths_of(&b0.backward),
+            paths_of(&b1.paths),
+        ];
+
+        // generate paths to connect incoming paths of both roads to outgoing ones
+        let ret = spawn_junction_full_connections(rg1, rg2)?;
+        junctions.push((*pt, a_in, *j, b_in, ret));
+    }
+
+    let spawned_a = pieces_a
+        .iter()
+        .map(|bp| spawn_road(commands, road_index, bp))
+        .collect::<Vec<_>>();
+    let spawned_b = pieces_b
+        .iter()
+        .map(|pieces| {
+            pieces
+                .iter()
+                .map(|bp| spawn_road(commands, road_index, bp))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    for (pt, a_in, j, b_in, ret) in junctions {
+        let (sa0, sa1) = (&spawned_a[a_in], &spawned_a[a_in + 1]);
+        let (sb0, sb1) = (&spawned_b[j][b_in], &spawned_b[j][b_in + 1]);
+        let i_paths = [&sa0.1, &sa1.2, &sb0.1, &sb1.2];
+        let o_paths = [&sa0.2, &sa1.1, &sb0.2, &sb1.1];
+        spawn_junction(
+            commands,
+            road_index,
+            JunctionBluePrint {
+                center: pt,
+                width: road.event.width().max(crossed[j].event.width()),
+                connections: ret
+                    .into_iter()
+                    .map(|((ir, ip, or, op), path)| {
+                        (Some(i_paths[ir][ip]), path, Some(o_paths[or][op]))
+                    })
+                    .collect::<Vec<(Option<Entity>, Path, Option<Entity>)>>(),
+            },
+        );
     }
     Ok(())
 }
 
-/// return road entity and paths entity
+/// spawn the paths of one direction under |road_e|, each with its neighbours as left and
+/// right, and link them. return the path entities in the same order.
+fn spawn_lanes(commands: &mut Commands, road_e: Entity, paths: Vec<Lane>) -> Vec<Entity> {
+    // entities first, so every lane can name its neighbours
+    let lane_es = paths
+        .iter()
+        .map(|_| commands.spawn_empty().id())
+        .collect::<Vec<_>>();
+    for (i, (from_es, path, next_es)) in paths.into_iter().enumerate() {
+        let path_e = lane_es[i];
+        commands
+            .entity(path_e)
+            .insert(Path {
+                left: i.checked_sub(1).map(|j| lane_es[j]),
+                right: lane_es.get(i + 1).copied(),
+                ..path
+            })
+            .set_parent(road_e);
+        for from_e in from_es {
+            path::link_next(commands, from_e, 1.0, path_e, 0.0);
+        }
+        for next_e in next_es {
+            path::link_next(commands, path_e, 1.0, next_e, 0.0);
+        }
+    }
+    lane_es
+}
+
+/// return road entity, forward paths entity and backward paths entity
 fn spawn_road(
-    mut commands: &mut Commands,
+    commands: &mut Commands,
     road_index: &mut RoadIndex,
     bp: &RoadBlueprint,
-) -> (Entity, Vec<Entity>) {
-    let paths = bp.paths.clone();
-    let mut paths_entities = vec![];
+) -> (Entity, Vec<Entity>, Vec<Entity>) {
     let event = bp.event.clone();
-    let road_e = commands
-        .spawn(Road {
-            center: event.center.clone(),
-            width: event.width,
-            speed_max: event.speed_max,
-            travel_time_avg: 1.,
-        })
-        .id();
-    println!("road {:?} has been spawned", road_e);
+    let road = Road {
+        center: event.center.clone(),
+        lanes: event.lanes,
+        speed_max: event.speed_max,
+        travel_time_avg: 1.,
+        spans: event.spans.clone(),
+    };
+    let road_e = commands.spawn(road.clone()).id();
 
-    for (from_e, path, next_e) in paths {
-        let path_e = commands.spawn(path).set_parent(road_e).id();
-        commands.entity(road_e).add_child(path_e);
-        paths_entities.push(path_e);
-        from_e.and_then(|from_e| path::link_next(&mut commands, from_e, 1.0, path_e, 0.0));
-        next_e.and_then(|next_e| path::link_next(&mut commands, path_e, 1.0, next_e, 0.0));
-    }
+    let forward_es = spawn_lanes(commands, road_e, bp.paths.clone());
+    let backward_es = spawn_lanes(commands, road_e, bp.backward.clone());
 
-    road_index.add_road(road_e);
-    println!("road_index: {:?}", road_index);
-    println!("spawn_road!");
-    return (road_e, paths_entities);
+    road_index.add_road(road_e, &road);
+    (road_e, forward_es, backward_es)
 }
 
 /// connections: ((P, V), (Q, U)) where P, Q is in and out points,
 /// V and U is direction vector. Entity is path entity
 fn spawn_junction(
-    mut commands: &mut Commands,
+    commands: &mut Commands,
     road_index: &mut RoadIndex,
     bp: JunctionBluePrint,
 ) -> Entity {
-    let (center, connections) = (bp.center, bp.connections);
-    let junction_e = commands.spawn(Junction { center }).id();
+    let footprint = bp.footprint();
+    let (center, width, connections) = (bp.center, bp.width, bp.connections);
+    let junction = Junction {
+        center,
+        width,
+        footprint,
+    };
+    let junction_e = commands.spawn(junction.clone()).id();
 
     for (from_path_e, path, next_path_e) in connections {
         let path_e = commands.spawn(path).set_parent(junction_e).id();
         next_path_e
-            .and_then(|next_path_e| path::link_next(&mut commands, path_e, 1.0, next_path_e, 0.0));
+            .and_then(|next_path_e| path::link_next(commands, path_e, 1.0, next_path_e, 0.0));
         from_path_e
-            .and_then(|from_path_e| path::link_next(&mut commands, from_path_e, 1.0, path_e, 0.0));
+            .and_then(|from_path_e| path::link_next(commands, from_path_e, 1.0, path_e, 0.0));
         commands.entity(junction_e).add_child(path_e);
         commands.entity(path_e).set_parent(junction_e);
     }
-    road_index.add_junction(junction_e);
+    road_index.add_junction(junction_e, &junction);
     junction_e
 }
 
-fn build_road_system(
-    mut commands: Commands,
-    mut road_index: ResMut<RoadIndex>,
-    road_query: Query<(&mut Road, Option<&Children>)>,
-    // path_query: Query<(&mut Path, &Children)>,
-    path_query: Query<(&mut Path, Option<&Children>)>,
-    // path_query_2: Query<&mut Path>,
-    next_query: Query<&PathNext>,
-    prev_query: Query<&PathPrev>,
-    mut events: EventReader<BuildRoad>,
-) {
-    for event in events.read() {
-        let mut flg = false;
-        // check if collision
-        // if collision, split existing road into two road and junction
-        // TODO: replace with real position
-        let road_bp = &RoadBlueprint {
-            event: event.clone(),
-            paths: vec![(
-                None,
-                Path {
-                    curve: event.center.clone(),
-                    ..default()
-                },
-                None,
-            )],
-        };
-        for other_road_e in road_index
-            .collisions(0., 0., 0., 0.)
-            .collect::<Vec<CollisionTarget>>()
-        {
-            match other_road_e {
-                CollisionTarget::Road(old_road_e) => {
-                    // check if it's collision
-                    println!(
-                        "{:?} should be road. actual: {:?}",
-                        old_road_e,
-                        road_query.get(old_road_e)
-                    );
-
-                    road_query
-                        .get(old_road_e)
-                        .ok()
-                        .and_then(|(road_other, children)| {
-                            let mut path_prev_next =
-                                HashMap::<Entity, (Option<Entity>, Path, Option<Entity>)>::new();
-                            if let Some(children) = children {
-                                children.iter()
-                            } else {
-                                [].iter()
-                            }
-                            .filter_map(|path_e| {
-                                let e = path_query.get(*path_e);
-                                if let Ok(i) = e {
-                                    println!("Some: {:?}", e);
-                                    Some((path_e, i.0, i.1))
-                                } else {
-                                    println!("err: {:?}", e);
-                                    None
-                                }
-                            })
-                            .for_each(|(path_e, path, children)| {
-                                let path_e = *path_e;
-                                path_prev_next.insert(old_road_e, (None, path.clone(), None));
-                                children.map_or_else(|| [].iter(), |e| e.iter()).for_each(
-                                    |next_or_prev_e| {
-                                        if next_query.get(*next_or_prev_e).is_ok()
-                                            && path_prev_next.contains_key(&path_e)
-                                        {
-                                            let tmp = &path_prev_next[&path_e].clone();
-                                            path_prev_next.remove(&path_e);
-                                            path_prev_next.insert(
-                                                path_e,
-                                                (tmp.0, tmp.1.clone(), Some(*next_or_prev_e)),
-                                            );
-                                        } else if prev_query.get(*next_or_prev_e).is_ok() {
-                                            let tmp = &path_prev_next[&path_e].clone();
-                                            path_prev_next.remove(&path_e);
-                                            path_prev_next.insert(
-                                                path_e,
-                                                (Some(*next_or_prev_e), tmp.1.clone(), tmp.2),
-                                            );
-                                        }
-                                    },
-                                )
-                            });
-                            road_bp.to_road().intersects(road_other).and_then(|_| {
-                                flg = true;
-                                Some((road_other, path_prev_next.into_iter().map(|(_, u)| u)))
-                            })
-                        })
-                        .and_then(|(road_other, paths)| {
-                            spawn_split_collision_roads(
-                                &mut commands,
-                                vec![
-                                    RoadBlueprint {
-                                        event: BuildRoad {
-                                            center: road_other.center.clone(),
-                                            width: road_other.width,
-                                            speed_max: road_other.speed_max,
-                                        },
-                                        paths: paths.map(|p| p.clone()).collect(),
-                                    },
-                                    road_bp.clone(),
-                                ],
-                                &mut road_index,
-                            )
-                            .ok()
-                        })
-                        .and_then(|_| {
-                            commands.entity(old_road_e).despawn_recursive();
-                            road_index.remove(old_road_e);
-                            Some(())
-                        });
-                }
-                CollisionTarget::Junction(e) => {
-                    // TODO
-                }
-            }
-        }
-        // and spawn two road, connect them to junction
-        // else just spawn road with no connection
-        if flg {
-            continue;
-        }
-        spawn_road(
-            &mut commands,
-            &mut road_index,
-            &RoadBlueprint {
-                event: event.clone(),
-                paths: vec![(
-                    None,
-                    Path {
-                        curve: event.center.clone(),
-                        ..default()
-                    },
-                    None,
-                )],
-            },
-        );
-    }
-}
+/// a lane into or out of a junction, and its path
+type ArmLane = (Entity, Path);
 
-pub fn show_debug_road(roads: Query<&Road>, mut gizmos: Gizmos) {
-    for road in roads.iter() {
-        road.center
-            .iter_positions(64)
-            .collect::<Vec<Vec3>>()
-            .windows(2)
-            .for_each(|p| gizmos.line(p[0], p[1], Color::BLACK));
-    }
+/// a junction a new road runs through, and what it takes to build it again with the road
+struct EnteredJunction {
+    entity: Entity,
+    junction: Junction,
+    /// the stretch of the new road inside the junction, by arc length
+    hole: (f32, f32),
+    /// the lanes coming in on each road that meets here. group k of incoming and outgoing
+    /// is the same road, as in [`spawn_junction_full_connections`]
+    incoming: Vec<Vec<ArmLane>>,
+    outgoing: Vec<Vec<ArmLane>>,
+    /// links from those lanes to the connectors that go away with the junction
+    stale_links: Vec<Entity>,
 }
 
-#[derive(Resource)]
-struct RoadBuildingState {
-    pts: Vec<Vec3>,
+/// the junctions |road| runs through, sorted along it, with the stretch inside each one.
+fn entered_junctions(
+    road: &Road,
+    road_index: &RoadIndex,
+    junction_query: &Query<(&Junction, Option<&Children>)>,
+) -> Vec<(Entity, Junction, (f32, f32))> {
+    let mut ret = road_index
+        .collisions(&road.bbox())
+        .into_iter()
+        .filter_map(|target| target.junction())
+        .filter_map(|e| junction_query.get(e).ok().map(|(j, _)| (e, j)))
+        .filter_map(|(e, junction)| Some((e, junction.clone(), junction.hole(road)?)))
+        .collect::<Vec<_>>();
+    ret.sort_by(|a, b| a.2 .0.total_cmp(&b.2 .0));
+    ret
 }
 
-impl RoadBuildingState {
-    fn new() -> Self {
-        Self { pts: Vec::new() }
+/// the stretches of a road of |length| around |holes|, which are sorted along it. there is
+/// one more than there are holes: stretch k runs up to hole k and on from hole k - 1.
+fn between_holes(length: f32, holes: impl Iterator<Item = (f32, f32)>) -> Vec<(f32, f32)> {
+    let mut start = 0.;
+    let mut ret = vec![];
+    for (hole_start, hole_end) in holes {
+        ret.push((start, hole_start));
+        start = hole_end;
     }
+    ret.push((start, length));
+    ret
 }
 
-pub struct RoadBuildingPlugin;
+/// the roads meeting at a junction, from the links of its connectors (|children|): the lanes
+/// into and out of the junction grouped by the road they are on, and the links from them to
+/// the connectors
+fn junction_arms(
+    children: Option<&Children>,
+    path_query: &Query<(&mut Path, Option<&Children>)>,
+    next_query: &Query<&PathNext>,
+    prev_query: &Query<&PathPrev>,
+    parent_query: &Query<&Parent>,
+) -> (Vec<Vec<ArmLane>>, Vec<Vec<ArmLane>>, Vec<Entity>) {
+    let connectors = children
+        .map_or(&[][..], |c| &c[..])
+        .iter()
+        .filter(|e| path_query.contains(**e))
+        .copied()
+        .collect::<Vec<_>>();
+    // (coming in, lane)
+    let mut lanes = vec![];
+    for (_, links) in connectors.iter().filter_map(|e| path_query.get(*e).ok()) {
+        for link_e in links.map_or(&[][..], |c| &c[..]) {
+            if let Ok(prev) = prev_query.get(*link_e) {
+                lanes.push((true, prev.prev));
+            } else if let Ok(next) = next_query.get(*link_e) {
+                lanes.push((false, next.next));
+            }
+        }
+    }
+
+    let mut roads: Vec<Entity> = vec![];
+    let (mut incoming, mut outgoing) = (vec![], vec![]);
+    let mut stale_links = vec![];
+    for (coming_in, lane_e) in lanes {
+        let (Ok(road), Ok((path, links))) = (parent_query.get(lane_e), path_query.get(lane_e))
+        else {
+            continue;
+        };
+        let k = roads
+            .iter()
+            .position(|e| *e == road.get())
+            .unwrap_or_else(|| {
+                roads.push(road.get());
+                incoming.push(vec![]);
+                outgoing.push(vec![]);
+                roads.len() - 1
+            });
+        let group: &mut Vec<ArmLane> = if coming_in {
+            &mut incoming[k]
+        } else {
+            &mut outgoing[k]
+        };
+        if group.iter().any(|(e, _)| *e == lane_e) {
+            continue;
+        }
+        group.push((lane_e, path.clone()));
+        stale_links.extend(
+            links
+                .map_or(&[][..], |c| &c[..])
+                .iter()
+                .filter(|link_e| {
+                    next_query
+                        .get(**link_e)
+                        .is_ok_and(|next| connectors.contains(&next.next))
+                        || prev_query
+                            .get(**link_e)
+                            .is_ok_and(|prev| connectors.contains(&prev.prev))
+                })
+                .copied(),
+        );
+    }
+    (incoming, outgoing, stale_links)
+}
+
+/// build |bp| into the junctions it runs through. the road is cut at their edges, and each
+/// junction is built again with the pieces before and after it as two more arms, connected
+/// to every other arm.
+fn merge_into_junctions(
+    commands: &mut Commands,
+    road_index: &mut RoadIndex,
+    bp: &RoadBlueprint,
+    junctions: Vec<EnteredJunction>,
+) -> Result<()> {
+    let width = bp.event.width();
+    let center = &bp.event.center;
+    let stretches = between_holes(center.local().length(), junctions.iter().map(|j| j.hole));
+    if stretches.iter().any(|(start, end)| start > end) {
+        return Err(anyhow!("the road runs through junctions that overlap"));
+    }
+    // a stretch too short to be a road is where the road ends inside a junction
+    let mut pieces = stretches
+        .iter()
+        .map(|(start, end)| {
+            if end - start <= width / 2. {
+                return Ok(None);
+            }
+            let piece = RoadBlueprint::new(BuildRoad {
+                center: PlacedCurve::new(
+                    center.origin(),
+                    center.local().slice_by_length(*start, *end)?,
+                ),
+                lanes: bp.event.lanes,
+                speed_max: bp.event.speed_max,
+                spans: slice_spans(&bp.event.spans, *start, *end),
+                start: None,
+                end: None,
+            });
+            Ok(Some(piece))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // the ends of the road may already be joined to other roads
+    if let Some(Some(first)) = pieces.first_mut() {
+        for (lane, (from_es, _, _)) in first.paths.iter_mut().zip(&bp.paths) {
+            lane.0 = from_es.clone();
+        }
+        for (lane, (_, _, next_es)) in first.backward.iter_mut().zip(&bp.backward) {
+            lane.2 = next_es.clone();
+        }
+    }
+    if let Some(Some(last)) = pieces.last_mut() {
+        for (lane, (_, _, next_es)) in last.paths.iter_mut().zip(&bp.paths) {
+            lane.2 = next_es.clone();
+        }
+        for (lane, (from_es, _, _)) in last.backward.iter_mut().zip(&bp.backward) {
+            lane.0 = from_es.clone();
+        }
+    }
+    let paths_of = |lanes: &Vec<Lane>| {
+        lanes
+            .iter()
+            .map(|(_, path, _)| path.clone())
+            .collect::<Vec<_>>()
+    };
+    let arm_paths = |groups: &Vec<Vec<ArmLane>>| {
+        groups
+            .iter()
+            .map(|lanes| lanes.iter().map(|(_, path)| path.clone()).collect())
+            .collect::<Vec<Vec<Path>>>()
+    };
+    // every junction is laid out before anything is spawned or despawned, so one that can't
+    // be leaves the network as it was
+    let connections = junctions
+        .iter()
+        .enumerate()
+        .map(|(k, entered)| {
+            // forward lanes come in on the piece before the junction and leave on the one
+            // after, backward lanes the other way round
+            let (mut incoming, mut outgoing) =
+                (arm_paths(&entered.incoming), arm_paths(&entered.outgoing));
+            if let Some(piece) = &pieces[k] {
+                incoming.push(paths_of(&piece.paths));
+                outgoing.push(paths_of(&piece.backward));
+            }
+            if let Some(piece) = &pieces[k + 1] {
+                incoming.push(paths_of(&piece.backward));
+                outgoing.push(paths_of(&piece.paths));
+            }
+            spawn_junction_full_connections(incoming, outgoing)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let spawned = pieces
+        .iter()
+        .map(|piece| {
+            piece
+                .as_ref()
+                .map(|bp| spawn_road(commands, road_index, bp))
+        })
+        .collect::<Vec<_>>();
+    let arm_lanes = |es: &Vec<Entity>, lanes: &Vec<Lane>| {
+        es.iter()
+            .zip(lanes.iter())
+            .map(|(e, (_, path, _))| (*e, path.clone()))
+            .collect::<Vec<ArmLane>>()
+    };
+
+    for (k, (mut entered, connections)) in junctions.into_iter().zip(connections).enumerate() {
+        // the arms in the same order as the connections were laid out in
+        if let (Some(piece), Some((_, forward_es, backward_es))) = (&pieces[k], &spawned[k]) {
+            entered.incoming.push(arm_lanes(forward_es, &piece.paths));
+            entered
+                .outgoing
+                .push(arm_lanes(backward_es, &piece.backward));
+        }
+        if let (Some(piece), Some((_, forward_es, backward_es))) = (&pieces[k + 1], &spawned[k + 1])
+        {
+            entered
+                .incoming
+                .push(arm_lanes(backward_es, &piece.backward));
+            entered.outgoing.push(arm_lanes(forward_es, &piece.paths));
+        }
+
+        for link_e in entered.stale_links {
+            commands.entity(link_e).despawn_recursive();
+        }
+        commands.entity(entered.entity).despawn_recursive();
+        road_index.remove(entered.entity);
+        spawn_junction(
+            commands,
+            road_index,
+            JunctionBluePrint {
+                center: entered.junction.center,
+                width: entered.junction.width.max(width),
+                connections: connections
+                    .into_iter()
+                    .map(|((ir, ip, or, op), path)| {
+                        (
+                            Some(entered.incoming[ir][ip].0),
+                            path,
+                            Some(entered.outgoing[or][op].0),
+                        )
+                    })
+                    .collect(),
+            },
+        );
+    }
+    Ok(())
+}
+
+/// a lane of a spawned road: its path entity, with its path and the paths it is linked from
+/// and to
+type SpawnedLane = (Entity, Lane);
+
+/// the lanes of a spawned road with all their links, in spawn order
+fn spawned_lanes(
+    children: Option<&Children>,
+    path_query: &Query<(&mut Path, Option<&Children>)>,
+    next_query: &Query<&PathNext>,
+    prev_query: &Query<&PathPrev>,
+) -> Vec<SpawnedLane> {
+    children
+        .map_or(&[][..], |c| &c[..])
+        .iter()
+        .filter_map(|path_e| path_query.get(*path_e).ok().map(|p| (*path_e, p)))
+        .map(|(path_e, (path, links))| {
+            let (mut prev_es, mut next_es) = (vec![], vec![]);
+            for link_e in links.map_or(&[][..], |c| &c[..]) {
+                if let Ok(next) = next_query.get(*link_e) {
+                    next_es.push(next.next);
+                } else if let Ok(prev) = prev_query.get(*link_e) {
+                    prev_es.push(prev.prev);
+                }
+            }
+            (path_e, (prev_es, path.clone(), next_es))
+        })
+        .collect()
+}
+
+/// the links from the lanes next to a road into its lanes (|children|), which are left
+/// pointing at nothing once the road is despawned. the links of its own lanes go with them.
+fn links_into(
+    children: Option<&Children>,
+    path_query: &Query<(&mut Path, Option<&Children>)>,
+    next_query: &Query<&PathNext>,
+    prev_query: &Query<&PathPrev>,
+) -> Vec<Entity> {
+    let lanes = children.map_or(&[][..], |c| &c[..]);
+    let mut ret = vec![];
+    for (lane_e, (_, links)) in lanes
+        .iter()
+        .filter_map(|e| path_query.get(*e).ok().map(|p| (*e, p)))
+    {
+        for link_e in links.map_or(&[][..], |c| &c[..]) {
+            // the lane at the other end of a link keeps the link back
+            let other_e = if let Ok(next) = next_query.get(*link_e) {
+                next.next
+            } else if let Ok(prev) = prev_query.get(*link_e) {
+                prev.prev
+            } else {
+                continue;
+            };
+            let Ok((_, other_links)) = path_query.get(other_e) else {
+                continue;
+            };
+            if lanes.contains(&other_e) {
+                continue;
+            }
+            ret.extend(
+                other_links
+                    .map_or(&[][..], |c| &c[..])
+                    .iter()
+                    .filter(|e| {
+                        next_query.get(**e).is_ok_and(|next| next.next == lane_e)
+                            || prev_query.get(**e).is_ok_and(|prev| prev.prev == lane_e)
+                    })
+                    .copied(),
+            );
+        }
+    }
+    ret
+}
+
+/// which way |path| runs along |road|, and how far right of the center it starts
+fn lane_place(road: &Road, path: &Path) -> (bool, f32) {
+    let p = path.curve.start();
+    let along = road.center.project(p).length;
+    let v = path.curve.velocity(0.);
+    let forward = v.dot(road.center.velocity((along / road.length()) as f64)) > 0.;
+    let right = Vec3::new(-v.z, 0., v.x).normalize_or_zero();
+    let offset = (p - road.center.position_at_length(along as f64)).as_vec3();
+    (forward, offset.dot(right))
+}
+
+/// the forward and backward lanes of |road|, each from the leftmost lane
+fn sort_lanes(road: &Road, lanes: Vec<SpawnedLane>) -> (Vec<SpawnedLane>, Vec<SpawnedLane>) {
+    let (mut forward, mut backward): (Vec<_>, Vec<_>) = lanes
+        .into_iter()
+        .map(|lane| (lane_place(road, &lane.1 .1), lane))
+        .partition(|((forward, _), _)| *forward);
+    for group in [&mut forward, &mut backward] {
+        group.sort_by(|a, b| a.0 .1.total_cmp(&b.0 .1));
+    }
+    let strip = |group: Vec<((bool, f32), SpawnedLane)>| group.into_iter().map(|(_, lane)| lane);
+    (strip(forward).collect(), strip(backward).collect())
+}
+
+/// a blueprint of |road| as it is, to be split and spawned again
+fn blueprint_of(road: &Road, lanes: Vec<SpawnedLane>) -> RoadBlueprint {
+    let (forward, backward) = sort_lanes(road, lanes);
+    let lanes_of = |group: Vec<SpawnedLane>| group.into_iter().map(|(_, lane)| lane).collect();
+    RoadBlueprint {
+        event: BuildRoad {
+            center: road.center.clone(),
+            lanes: road.lanes,
+            speed_max: road.speed_max,
+            spans: road.spans.clone(),
+            start: None,
+            end: None,
+        },
+        paths: lanes_of(forward),
+        backward: lanes_of(backward),
+    }
+}
+
+/// link the lanes at the ends of |bp| that are snapped to the end of another road to the
+/// lanes of that road, innermost to innermost. |lanes_of| gives the forward and backward
+/// lanes of a road.
+fn join_road_ends(bp: &mut RoadBlueprint, lanes_of: impl Fn(Entity) -> (Vec<Entity>, Vec<Entity>)) {
+    for (snap, at_start) in [(bp.event.start, true), (bp.event.end, false)] {
+        let Some(Snap::RoadEnd { road, at_end }) = snap else {
+            continue;
+        };
+        // the lanes of the other road that end where the roads meet, and those that start
+        let (forward, backward) = lanes_of(road);
+        let (ending, starting) = if at_end {
+            (forward, backward)
+        } else {
+            (backward, forward)
+        };
+        if at_start {
+            for (lane, e) in bp.paths.iter_mut().zip(ending) {
+                lane.0.push(e);
+            }
+            for (lane, e) in bp.backward.iter_mut().zip(starting) {
+                lane.2.push(e);
+            }
+        } else {
+            for (lane, e) in bp.paths.iter_mut().zip(starting) {
+                lane.2.push(e);
+            }
+            for (lane, e) in bp.backward.iter_mut().zip(ending) {
+                lane.0.push(e);
+            }
+        }
+    }
+}
+
+/// a road split around a junction that only joins its two halves, laid out to be spawned
+struct Tee {
+    road_a: RoadBlueprint,
+    junction_bp: JunctionBluePrint,
+    road_b: RoadBlueprint,
+    connections: HashMap<(usize, usize, usize, usize), Path>,
+}
+
+/// lay out |through| split at |at| around a junction that only joins its two halves, so a
+/// road can be built into it later
+fn lay_out_tee(through: RoadBlueprint, at: DVec3) -> Result<Tee> {
+    let (road_a, junction_bp, road_b) = split_road(through, at)?;
+    let paths_of = |lanes: &Vec<Lane>| {
+        lanes
+            .iter()
+            .map(|(_, path, _)| path.clone())
+            .collect::<Vec<_>>()
+    };
+    let connections = spawn_junction_full_connections(
+        vec![paths_of(&road_a.paths), paths_of(&road_b.backward)],
+        vec![paths_of(&road_a.backward), paths_of(&road_b.paths)],
+    )?;
+    Ok(Tee {
+        road_a,
+        junction_bp,
+        road_b,
+        connections,
+    })
+}
+
+/// spawn the halves of |tee| and the junction between them
+fn spawn_tee(commands: &mut Commands, road_index: &mut RoadIndex, tee: Tee) {
+    let (_, a_forward, a_backward) = spawn_road(commands, road_index, &tee.road_a);
+    let (_, b_forward, b_backward) = spawn_road(commands, road_index, &tee.road_b);
+    let (incoming, outgoing) = ([a_forward, b_backward], [a_backward, b_forward]);
+    spawn_junction(
+        commands,
+        road_index,
+        JunctionBluePrint {
+            connections: tee
+                .connections
+                .into_iter()
+                .map(|((ir, ip, or, op), path)| {
+                    (Some(incoming[ir][ip]), path, Some(outgoing[or][op]))
+                })
+                .collect(),
+            ..tee.junction_bp
+        },
+    );
+}
+
+/// how deep, relative to its width, a new road may cut into the network without crossing
+const OVERLAP_SLACK: f32 = 0.1;
+
+/// whether |road| would be laid over existing roads it doesn't cross, or over a junction it
+/// doesn't run through. a road that enters junctions mustn't cross any road either, that
+/// would take splitting it twice over.
+///
+/// a width is left out at both ends of the road and of the stretches between the junctions
+/// it enters, so roads may still meet end to end at an angle.
+fn overlaps_network(
+    road: &Road,
+    road_index: &RoadIndex,
+    road_query: &Query<(&mut Road, Option<&Children>)>,
+    junction_query: &Query<(&Junction, Option<&Children>)>,
+) -> bool {
+    let entered = entered_junctions(road, road_index, junction_query);
+    let footprints = between_holes(road.length(), entered.iter().map(|(_, _, hole)| *hole))
+        .into_iter()
+        .filter_map(|(start, end)| {
+            let center = road
+                .center
+                .local()
+                .slice_by_length(start + road.width(), end - road.width())
+                .ok()?;
+            Some(CurvePair::from_center(&center, road.width()))
+        })
+        .collect::<Vec<_>>();
+    let cuts = |other: &dyn Fn(&CurvePair) -> Option<Penetration>| {
+        footprints
+            .iter()
+            .any(|f| other(f).is_some_and(|p| p.depth > road.width() * OVERLAP_SLACK))
+    };
+    // the footprints are around the origin of the road, and so must be everything they meet
+    let origin = road.center.origin();
+    let near = road_index.collisions(&road.bbox());
+    near.iter()
+        .filter_map(|target| road_query.get(target.road()?).ok())
+        .any(|(other, _)| {
+            let crossings = road.crossings(other);
+            (!entered.is_empty() && !crossings.is_empty())
+                || (cuts(&|f| f.penetration(&other.rebase(origin).footprint()))
+                    && crossings.is_empty()
+                    && road.grade_separations(other).is_empty())
+        })
+        || near
+            .iter()
+            .filter_map(|target| target.junction())
+            .filter(|e| entered.iter().all(|(entered_e, _, _)| entered_e != e))
+            .filter_map(|e| junction_query.get(e).ok())
+            .any(|(junction, _)| {
+                let over = road.center.project(junction.center).length;
+                let gap = road.center.position_at_length(over as f64).y - junction.center.y;
+                gap.abs() < CLEARANCE as f64
+                    && cuts(&|f| f.penetration(&junction.footprint_at(origin)))
+            })
+}
+
+/// the roads, junctions and lanes [`build_road_system`] builds new roads into
+#[derive(SystemParam)]
+struct RoadNetwork<'w, 's> {
+    road_query: Query<'w, 's, (&'static mut Road, Option<&'static Children>)>,
+    junction_query: Query<'w, 's, (&'static Junction, Option<&'static Children>)>,
+    path_query: Query<'w, 's, (&'static mut Path, Option<&'static Children>)>,
+    next_query: Query<'w, 's, &'static PathNext>,
+    prev_query: Query<'w, 's, &'static PathPrev>,
+    parent_query: Query<'w, 's, &'static Parent>,
+}
+
+fn build_road_system(
+    mut commands: Commands,
+    mut road_index: ResMut<RoadIndex>,
+    network: RoadNetwork,
+    mut events: EventReader<BuildRoad>,
+    // roads waiting for the T-junctions they end at to be spawned
+    mut deferred: Local<Vec<BuildRoad>>,
+) {
+    let RoadNetwork {
+        road_query,
+        junction_query,
+        path_query,
+        next_query,
+        prev_query,
+        parent_query,
+    } = network;
+    let pending = deferred.drain(..).collect::<Vec<_>>();
+    for event in pending.iter().chain(events.read()) {
+        // pass over or under roads far enough apart in height instead of meeting them
+        let mut event = event.clone();
+        let mut new_road = Road {
+            center: event.center.clone(),
+            lanes: event.lanes,
+            speed_max: event.speed_max,
+            travel_time_avg: 1.,
+            spans: event.spans.clone(),
+        };
+        road_index
+            .collisions(&new_road.bbox())
+            .into_iter()
+            .filter_map(|target| road_query.get(target.road()?).ok())
+            .for_each(|(other, _)| new_road.add_spans_over(other));
+        event.spans = new_road.spans;
+        let event = &event;
+
+        let mut road_bp = RoadBlueprint::new(event.clone());
+        if overlaps_network(
+            &road_bp.to_road(),
+            &road_index,
+            &road_query,
+            &junction_query,
+        ) {
+            warn!("road overlaps the network, skipped");
+            continue;
+        }
+
+        // a road ending on another one first splits it around a junction, then is built into
+        // that junction like into any other once it is spawned, the next time round
+        let tees = [event.start, event.end]
+            .into_iter()
+            .filter_map(|snap| match snap {
+                Some(Snap::OnRoad { road, at }) => Some((road, at)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if !tees.is_empty() {
+            let new_road = road_bp.to_road();
+            let crosses = road_index
+                .collisions(&new_road.bbox())
+                .into_iter()
+                .filter_map(|target| road_query.get(target.road()?).ok())
+                .any(|(other, _)| !new_road.crossings(other).is_empty());
+            if crosses || (tees.len() == 2 && tees[0].0 == tees[1].0) {
+                warn!("a road ending on another one can't cross roads or end on it twice");
+                continue;
+            }
+            let Ok(throughs) = tees
+                .iter()
+                .map(|(road_e, at)| road_query.get(*road_e).map(|r| (*road_e, r, *at)))
+                .collect::<Result<Vec<_>, _>>()
+            else {
+                warn!("the road to end on is gone, skipped");
+                continue;
+            };
+            // every tee is laid out before anything is spawned or despawned. a road with no
+            // junction to end in would be built loose, so it is dropped and the network left
+            // as it was
+            let laid_out = throughs
+                .into_iter()
+                .map(|(through_e, (through, children), at)| {
+                    let lanes = spawned_lanes(children, &path_query, &next_query, &prev_query);
+                    let stale_links = links_into(children, &path_query, &next_query, &prev_query);
+                    let tee = lay_out_tee(blueprint_of(through, lanes), at)?;
+                    Ok((through_e, stale_links, tee))
+                })
+                .collect::<Result<Vec<_>>>();
+            let laid_out = match laid_out {
+                Ok(laid_out) => laid_out,
+                Err(err) => {
+                    warn!("can't split the road to end on: {}", err);
+                    continue;
+                }
+            };
+            for (through_e, stale_links, tee) in laid_out {
+                spawn_tee(&mut commands, &mut road_index, tee);
+                for link_e in stale_links {
+                    commands.entity(link_e).despawn_recursive();
+                }
+                commands.entity(through_e).despawn_recursive();
+                road_index.remove(through_e);
+            }
+            let mut event = event.clone();
+            for snap in [&mut event.start, &mut event.end] {
+                if let Some(Snap::OnRoad { .. }) = snap {
+                    *snap = None;
+                }
+            }
+            deferred.push(event);
+            continue;
+        }
+        join_road_ends(&mut road_bp, |road_e| {
+            let Ok((road, children)) = road_query.get(road_e) else {
+                return (vec![], vec![]);
+            };
+            let lanes = spawned_lanes(children, &path_query, &next_query, &prev_query);
+            let (forward, backward) = sort_lanes(road, lanes);
+            let es = |group: Vec<SpawnedLane>| group.into_iter().map(|(e, _)| e).collect();
+            (es(forward), es(backward))
+        });
+        let road_bp = &road_bp;
+        // the roads the new one crosses are all split with it at once
+        let new_road = road_bp.to_road();
+        let crossed = road_index
+            .collisions(&new_road.bbox())
+            .into_iter()
+            .filter_map(|target| target.road())
+            .filter_map(|e| road_query.get(e).ok().map(|r| (e, r)))
+            .filter(|(_, (other, _))| {
+                let same = new_road.center.start().distance(other.center.start()) < 1e-6
+                    && new_road.center.end().distance(other.center.end()) < 1e-6;
+                !same && new_road.intersects(other).is_some()
+            })
+            .map(|(e, (other, children))| {
+                let lanes = spawned_lanes(children, &path_query, &next_query, &prev_query);
+                let stale_links = links_into(children, &path_query, &next_query, &prev_query);
+                ((e, stale_links), blueprint_of(other, lanes))
+            })
+            .collect::<Vec<_>>();
+        if !crossed.is_empty() {
+            let (crossed_es, crossed_bps): (Vec<_>, Vec<_>) = crossed.into_iter().unzip();
+            match spawn_split_collision_roads(
+                &mut commands,
+                &mut road_index,
+                road_bp.clone(),
+                crossed_bps,
+            ) {
+                Ok(()) => {
+                    for (old_road_e, stale_links) in crossed_es {
+                        for link_e in stale_links {
+                            commands.entity(link_e).despawn_recursive();
+                        }
+                        commands.entity(old_road_e).despawn_recursive();
+                        road_index.remove(old_road_e);
+                    }
+                }
+                Err(err) => warn!("can't split the roads: {}", err),
+            }
+            continue;
+        }
+        // roads that enter junctions cross no other roads, overlaps_network made sure of that
+        let mut entered = vec![];
+        for e in road_index
+            .collisions(&new_road.bbox())
+            .into_iter()
+            .filter_map(|target| target.junction())
+        {
+            let Ok((junction, children)) = junction_query.get(e) else {
+                continue;
+            };
+            let Some(hole) = junction.hole(&new_road) else {
+                continue;
+            };
+            let (incoming, outgoing, stale_links) = junction_arms(
+                children,
+                &path_query,
+                &next_query,
+                &prev_query,
+                &parent_query,
+            );
+            entered.push(EnteredJunction {
+                entity: e,
+                junction: junction.clone(),
+                hole,
+                incoming,
+                outgoing,
+                stale_links,
+            });
+        }
+        if !entered.is_empty() {
+            entered.sort_by(|a, b| a.hole.0.total_cmp(&b.hole.0));
+            if let Err(err) = merge_into_junctions(&mut commands, &mut road_index, road_bp, entered)
+            {
+                warn!("can't build the road into the junction: {}", err);
+            }
+            continue;
+        }
+        spawn_road(&mut commands, &mut road_index, road_bp);
+    }
+}
+
+pub fn show_debug_road(roads: Query<&Road>, origin: Res<RenderOrigin>, mut gizmos: Gizmos) {
+    for road in roads.iter() {
+        let center = road.center.relative_to(&origin);
+        center
+            .to_polyline(DEBUG_TOLERANCE)
+            .windows(2)
+            .for_each(|p| gizmos.line(p[0], p[1], Color::BLACK));
+        for span in road.spans.iter() {
+            show_debug_span(road, span, &origin, &mut gizmos);
+        }
+    }
+}
+
+/// a bridge shows its deck edges and piers down to the ground, a tunnel its two portals
+fn show_debug_span(road: &Road, span: &Span, origin: &FloatingOrigin, gizmos: &mut Gizmos) {
+    let center = road.center.relative_to(origin);
+    let ground = origin.to_render(DVec3::ZERO).y;
+    let side = |length: f32| {
+        let v = center.velocity(length / road.length());
+        let right = Vec3::new(-v.z, 0., v.x).normalize_or_zero() * road.width() / 2.;
+        let p = center.position_at_length(length);
+        (p - right, p + right)
+    };
+    match span.kind {
+        SpanKind::Bridge => {
+            let Ok(deck) = center.slice_by_length(span.start, span.end) else {
+                return;
+            };
+            let edges = deck
+                .flatten(DEBUG_TOLERANCE)
+                .into_iter()
+                .map(|(length, _)| side(span.start + length))
+                .collect::<Vec<_>>();
+            let n = edges.len() - 1;
+            edges.windows(2).for_each(|e| {
+                gizmos.line(e[0].0, e[1].0, Color::ORANGE);
+                gizmos.line(e[0].1, e[1].1, Color::ORANGE);
+            });
+            for (left, right) in [edges[0], edges[n / 2], edges[n]] {
+                gizmos.line(left, Vec3::new(left.x, ground, left.z), Color::ORANGE);
+                gizmos.line(right, Vec3::new(right.x, ground, right.z), Color::ORANGE);
+            }
+        }
+        SpanKind::Tunnel => {
+            for length in [span.start, span.end] {
+                let (left, right) = side(length);
+                let up = Vec3::Y * CLEARANCE;
+                gizmos.line(left, left + up, Color::DARK_GRAY);
+                gizmos.line(left + up, right + up, Color::DARK_GRAY);
+                gizmos.line(right + up, right, Color::DARK_GRAY);
+            }
+        }
+    }
+}
+
+/// the steepest grade the road tool builds
+const MAX_GRADE: f32 = 0.1;
+
+/// how far from the cursor the road tool looks for roads and junctions to snap to
+const SNAP_DISTANCE: f32 = 2.;
+
+#[derive(Resource)]
+struct RoadBuildingState {
+    pts: Vec<DVec3>,
+    /// height above the ground of the next point, changed with page up / page down
+    elevation: f32,
+    /// what the first point is snapped to
+    start: Option<Snap>,
+}
+
+impl RoadBuildingState {
+    fn new() -> Self {
+        Self {
+            pts: Vec::new(),
+            elevation: 0.,
+            start: None,
+        }
+    }
+}
+
+/// whether nothing is joined at |end| of |road_e| yet: no junction and no other road's end
+fn is_free_end(
+    end: DVec3,
+    road_e: Entity,
+    road_index: &RoadIndex,
+    road_query: &Query<&Road>,
+    junction_query: &Query<&Junction>,
+) -> bool {
+    const EPS: f32 = 1e-3;
+    road_index
+        .within_radius(end, EPS)
+        .into_iter()
+        .all(|target| match target {
+            CollisionTarget::Road(e) => {
+                e == road_e
+                    || road_query.get(e).map_or(true, |other| {
+                        [other.center.start(), other.center.end()]
+                            .iter()
+                            .all(|p| p.distance(end) > EPS as f64)
+                    })
+            }
+            CollisionTarget::Junction(e) => junction_query.get(e).map_or(true, |j| {
+                ground((j.center - end).as_vec3()).length() > j.radius() + EPS
+            }),
+        })
+}
+
+/// what the road tool snaps |pt| to, and where that puts the end of the road. a junction the
+/// cursor is in comes first, then a free road end nearby, then a point along the nearest road
+/// with room for a junction.
+fn find_snap(
+    pt: DVec3,
+    road_index: &RoadIndex,
+    road_query: &Query<&Road>,
+    junction_query: &Query<&Junction>,
+) -> Option<(Snap, DVec3)> {
+    let distance = |p: DVec3| ground((p - pt).as_vec3()).length();
+    let junction = road_index
+        .within_radius(pt, SNAP_DISTANCE)
+        .into_iter()
+        .filter_map(|target| target.junction())
+        .filter_map(|e| junction_query.get(e).ok().map(|j| (e, j)))
+        .filter(|(_, j)| distance(j.center) <= j.radius())
+        .min_by(|a, b| distance(a.1.center).total_cmp(&distance(b.1.center)));
+    if let Some((e, j)) = junction {
+        return Some((Snap::Junction(e), j.center));
+    }
+
+    let (road_e, projection) = road_index.nearest_road(pt, SNAP_DISTANCE)?;
+    let road = road_query.get(road_e).ok()?;
+    let end = [(false, road.center.start()), (true, road.center.end())]
+        .into_iter()
+        .filter(|(_, p)| distance(*p) <= SNAP_DISTANCE)
+        .min_by(|a, b| distance(a.1).total_cmp(&distance(b.1)));
+    if let Some((at_end, p)) = end {
+        return is_free_end(p, road_e, road_index, road_query, junction_query).then_some((
+            Snap::RoadEnd {
+                road: road_e,
+                at_end,
+            },
+            p,
+        ));
+    }
+    let room = road.width();
+    (projection.length > room && road.length() - projection.length > room).then(|| {
+        let at = road.center.position_at_length(projection.length as f64);
+        (Snap::OnRoad { road: road_e, at }, at)
+    })
+}
+
+/// highlight what the road tool snaps to: the junction, the end of the road or a line across
+/// the road where it will be split
+fn show_snap(
+    snap: Snap,
+    at: DVec3,
+    road_query: &Query<&Road>,
+    junction_query: &Query<&Junction>,
+    origin: &FloatingOrigin,
+    gizmos: &mut Gizmos,
+) {
+    const COLOR: Color = Color::CYAN;
+    let p = origin.to_render(at);
+    match snap {
+        Snap::Junction(e) => {
+            if let Ok(junction) = junction_query.get(e) {
+                gizmos.circle(p, Direction3d::Y, junction.radius(), COLOR);
+            }
+        }
+        Snap::RoadEnd { road, .. } => {
+            if let Ok(road) = road_query.get(road) {
+                gizmos.circle(p, Direction3d::Y, road.width() / 2., COLOR);
+            }
+        }
+        Snap::OnRoad { road, .. } => {
+            if let Ok(road) = road_query.get(road) {
+                let along = road.center.project(at).length;
+                let v = road.center.velocity((along / road.length()) as f64);
+                let right = Vec3::new(-v.z, 0., v.x).normalize_or_zero() * road.width() / 2.;
+                gizmos.line(p - right, p + right, COLOR);
+                gizmos.circle(p, Direction3d::Y, road.width() / 4., COLOR);
+            }
+        }
+    }
+}
+
+/// the road through |p0| and |p2| bent towards |p1|. heights of the points become a
+/// vertical profile: a straight grade, or a crest or sag at the station closest to |p1|.
+fn road_center(p0: Vec3, p1: Vec3, p2: Vec3) -> geometry::Result<Curve> {
+    let ground = |a: Vec3| Vec3::new(a.x, 0., a.z);
+    let (g0, g1, g2) = (ground(p0), ground(p1), ground(p2));
+    // straight roads don't need the Bézier arc-length math
+    let plan = if (g1 - g0).cross(g2 - g0).length() < 1e-3 * (g2 - g0).length_squared() {
+        LineSegment::try_new([g0, g2])?.to_curve()
+    } else {
+        QuadraticBezierCurve::try_new([g0, g1, g2])?.to_curve()
+    };
+    if p0.y == 0. && p1.y == 0. && p2.y == 0. {
+        return Ok(plan);
+    }
+    let length = plan.length();
+    let station = plan.project(g1).length;
+    let mut pvis = vec![Pvi::new(0., p0.y, 0.)];
+    if station > 1e-3 && length - station > 1e-3 {
+        pvis.push(Pvi::new(station, p1.y, 2. * station.min(length - station)));
+    }
+    pvis.push(Pvi::new(length, p2.y, 0.));
+    Ok(plan.with_profile(&VerticalProfile::new(pvis, MAX_GRADE)?))
+}
+
+pub struct RoadBuildingPlugin;
+
+/// where on the ground the cursor points
+#[derive(SystemParam)]
+struct GroundCursor<'w, 's> {
+    windows: Query<'w, 's, &'static Window>,
+    camera_query: Query<'w, 's, (&'static Camera, &'static GlobalTransform)>,
+    ground_query: Query<'w, 's, &'static GlobalTransform, With<Ground>>,
+    /// what the camera sees is relative to it
+    origin: Res<'w, RenderOrigin>,
+}
+
+impl GroundCursor<'_, '_> {
+    /// in render coordinates
+    fn point(&self) -> Option<Vec3> {
+        let cursor_position = self.windows.single().cursor_position()?;
+        let (camera, camera_transform) = self.camera_query.single();
+        let ground = self.ground_query.single();
+
+        // Calculate a ray pointing from the camera into the world based on the cursor's position.
+        let ray = camera.viewport_to_world(camera_transform, cursor_position)?;
+        let distance = ray.intersect_plane(ground.translation(), Plane3d::new(ground.up()))?;
+        Some(ray.get_point(distance))
+    }
+}
+
+/// the roads and junctions the road tool snaps to
+#[derive(SystemParam)]
+struct SnapTargets<'w, 's> {
+    road_index: Res<'w, RoadIndex>,
+    road_query: Query<'w, 's, &'static Road>,
+    junction_query: Query<'w, 's, &'static Junction>,
+}
 
 fn build_road_building_system(
     mut state: ResMut<RoadBuildingState>,
     mut events: EventWriter<BuildRoad>,
-    ground_query: Query<&GlobalTransform, With<Ground>>,
-    camera_query: Query<(&Camera, &GlobalTransform)>,
+    cursor: GroundCursor,
     mouse_event: Res<ButtonInput<MouseButton>>,
     keys: Res<ButtonInput<KeyCode>>,
-    windows: Query<&Window>,
+    targets: SnapTargets,
     mut gizmos: Gizmos,
 ) {
-    let Some(cursor_position) = windows.single().cursor_position() else {
+    let Some(ground_point) = cursor.point() else {
         return;
     };
-    let (camera, camera_transform) = camera_query.single();
-    let ground = ground_query.single();
-
-    // Calculate a ray pointing from the camera into the world based on the cursor's position.
-    let Some(ray) = camera.viewport_to_world(camera_transform, cursor_position) else {
-        return;
-    };
-    let Some(distance) = ray.intersect_plane(ground.translation(), Plane3d::new(ground.up()))
-    else {
-        return;
+    let origin = &cursor.origin;
+    let SnapTargets {
+        road_index,
+        road_query,
+        junction_query,
+    } = &targets;
+    if keys.just_pressed(KeyCode::PageUp) {
+        state.elevation += 1.;
+    }
+    if keys.just_pressed(KeyCode::PageDown) {
+        state.elevation -= 1.;
+    }
+    let ground_point = origin.to_world(ground_point);
+    let mut point = ground_point + DVec3::Y * state.elevation as f64;
+    if state.elevation != 0. {
+        gizmos.line(
+            origin.to_render(ground_point),
+            origin.to_render(point),
+            Color::GRAY,
+        );
+    }
+    // the ends of the road snap, the point it bends towards doesn't
+    let snap = if state.pts.len() == 1 {
+        None
+    } else {
+        find_snap(point, road_index, road_query, junction_query)
     };
-    let point = ray.get_point(distance);
+    if let Some((snap, at)) = snap {
+        show_snap(snap, at, road_query, junction_query, origin, &mut gizmos);
+        point = at;
+    }
     if (keys.pressed(KeyCode::ControlLeft) || keys.pressed(KeyCode::ControlRight))
         && mouse_event.just_pressed(MouseButton::Left)
     {
         state.pts.push(point);
+        if state.pts.len() == 1 {
+            state.start = snap.map(|(snap, _)| snap);
+        }
     }
     if keys.pressed(KeyCode::Escape) {
         state.pts.clear();
     }
     if state.pts.len() == 3 {
         // send event to build road
-        let curve = QuadraticBezierCurve::new([state.pts[0], state.pts[1], state.pts[2]]);
-        let width = 2.0;
-        let speed_max = 10.;
-        events.send(BuildRoad {
-            center: curve.to_curve(),
-            width,
-            speed_max,
-        });
-        state.pts.clear();
+        let pts = [state.pts[0], state.pts[1], state.pts[2]];
+        match PlacedCurve::from_world(pts, |[p0, p1, p2]| road_center(p0, p1, p2)) {
+            Ok(center) => {
+                let lanes = Lanes::two_way(1, 1.5, 0.25);
+                let speed_max = 10.;
+                events.send(BuildRoad {
+                    center,
+                    lanes,
+                    speed_max,
+                    spans: vec![],
+                    start: state.start,
+                    end: snap.map(|(snap, _)| snap),
+                });
+                state.pts.clear();
+            }
+            // keep the first points so only the end has to be picked again
+            Err(GeometryError::GradeTooSteep { grade, max }) => {
+                warn!(
+                    "the road climbs {:.1}%, at most {:.1}% is allowed",
+                    grade.abs() * 100.,
+                    max * 100.
+                );
+                state.pts.pop();
+            }
+            Err(err @ GeometryError::Degenerate { .. }) => {
+                warn!("can't build the road: {}", err);
+                state.pts.pop();
+            }
+            Err(err) => {
+                warn!("can't build the road: {}", err);
+                state.pts.clear();
+            }
+        }
     }
     state.pts.windows(2).for_each(|pts| {
-        gizmos.line(pts[0], pts[1], Color::RED);
+        gizmos.line(
+            origin.to_render(pts[0]),
+            origin.to_render(pts[1]),
+            Color::RED,
+        );
     });
 }
 
@@ -658,6 +2039,572 @@ impl Plugin for RoadPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(RoadIndex::new());
         app.add_event::<BuildRoad>();
-        app.add_systems(PostUpdate, build_road_system);
+        app.add_systems(Startup, setup_road_material);
+        app.add_systems(PostUpdate, (build_road_system, update_road_meshes).chain());
+        app.add_systems(Update, show_debug_road);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        ecs::system::SystemState,
+        math::{dvec3, vec3},
+    };
+
+    use super::*;
+
+    fn straight(p: DVec3, q: DVec3) -> PlacedCurve {
+        PlacedCurve::line([p, q]).unwrap()
+    }
+
+    fn build_road(center: PlacedCurve, start: Option<Snap>, end: Option<Snap>) -> BuildRoad {
+        BuildRoad {
+            center,
+            lanes: Lanes::two_way(1, 1.5, 0.25),
+            speed_max: 10.,
+            spans: vec![],
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn test_build_into_junction() {
+        let mut world = World::new();
+        world.insert_resource(RoadIndex::new());
+        world.init_resource::<Events<BuildRoad>>();
+        let mut system = IntoSystem::into_system(build_road_system);
+        system.initialize(&mut world);
+        let mut run = |world: &mut World, events: Vec<BuildRoad>| {
+            world.send_event_batch(events);
+            system.run((), world);
+            system.apply_deferred(world);
+        };
+        let count = |world: &mut World| {
+            let roads = world.query::<&Road>().iter(world).count();
+            let junctions = world.query::<&Junction>().iter(world).count();
+            (roads, junctions)
+        };
+
+        run(
+            &mut world,
+            vec![build_road(
+                straight(dvec3(-20., 0., 0.), dvec3(20., 0., 0.)),
+                None,
+                None,
+            )],
+        );
+        let through = world.query::<(Entity, &Road)>().single(&world).0;
+        assert_eq!(count(&mut world), (1, 0));
+
+        // a road ending on the first one splits it around a junction first
+        let at = dvec3(0., 0., 0.);
+        run(
+            &mut world,
+            vec![build_road(
+                straight(dvec3(0., 0., 20.), at),
+                None,
+                Some(Snap::OnRoad { road: through, at }),
+            )],
+        );
+        assert_eq!(count(&mut world), (2, 1));
+        let (junction_e, junction) = world.query::<(Entity, &Junction)>().single(&world);
+        let junction = junction.clone();
+        assert_eq!(world.get::<Children>(junction_e).unwrap().len(), 2);
+
+        // and is built into it the next time round, connected to both halves both ways
+        run(&mut world, vec![]);
+        assert_eq!(count(&mut world), (3, 1));
+        let (junction_e, rebuilt) = world.query::<(Entity, &Junction)>().single(&world);
+        assert_eq!(rebuilt.center, junction.center);
+        let connectors = world.get::<Children>(junction_e).unwrap().to_vec();
+        assert_eq!(connectors.len(), 6);
+        let mut links = world.query::<(&PathNext, &Parent)>();
+        for connector in connectors {
+            // one link into the connector from a lane, one out of it to a lane
+            let into = links
+                .iter(&world)
+                .filter(|(next, _)| next.next == connector)
+                .count();
+            let out = links
+                .iter(&world)
+                .filter(|(_, parent)| parent.get() == connector)
+                .count();
+            assert_eq!((into, out), (1, 1));
+        }
+        let new_road = world
+            .query::<&Road>()
+            .iter(&world)
+            .find(|road| road.center.start().z > 1.)
+            .unwrap();
+        assert!(new_road.center.end().z > 1.);
+        assert!(junction.hole(new_road).is_none());
+        assert_eq!(world.query::<&Path>().iter(&world).count(), 6 + 3 * 2);
+
+        // a road ending in the junction is built into it as a fourth arm
+        run(
+            &mut world,
+            vec![build_road(
+                straight(dvec3(-20., 0., -20.), dvec3(0., 0., 0.)),
+                None,
+                Some(Snap::Junction(junction_e)),
+            )],
+        );
+        assert_eq!(count(&mut world), (4, 1));
+        let junction_e = world.query::<(Entity, &Junction)>().single(&world).0;
+        // four other arms from each of four arms, one lane each way
+        assert_eq!(world.get::<Children>(junction_e).unwrap().len(), 4 * 3);
+    }
+
+    #[test]
+    fn test_far_from_origin() {
+        let mut world = World::new();
+        world.insert_resource(RoadIndex::new());
+        world.init_resource::<Events<BuildRoad>>();
+        let mut system = IntoSystem::into_system(build_road_system);
+        system.initialize(&mut world);
+        let mut run = |world: &mut World, events: Vec<BuildRoad>| {
+            world.send_event_batch(events);
+            system.run((), world);
+            system.apply_deferred(world);
+        };
+
+        // where an f32 is only good to a quarter of a meter
+        let at = dvec3(2_000_000.3, 0., -2_000_000.7);
+        for d in [DVec3::X, DVec3::Z] {
+            run(
+                &mut world,
+                vec![build_road(straight(at - d * 20., at + d * 20.), None, None)],
+            );
+        }
+
+        assert_eq!(world.query::<&Road>().iter(&world).count(), 4);
+        let junction = world.query::<&Junction>().single(&world);
+        assert!(junction.center.distance(at) < 1e-3);
+        // every lane runs on into the next one, through the connectors of the junction
+        let mut links = world.query::<(&PathNext, &Parent)>();
+        let mut paths = world.query::<&Path>();
+        let links = links
+            .iter(&world)
+            .map(|(next, parent)| (parent.get(), next.next))
+            .collect::<Vec<_>>();
+        assert_eq!(links.len(), 2 * 4 * 3);
+        for (from, to) in links {
+            let end = paths.get(&world, from).unwrap().curve.end();
+            let start = paths.get(&world, to).unwrap().curve.start();
+            assert!(end.distance(start) < 1e-3, "{:?} -> {:?}", end, start);
+        }
+    }
+
+    #[test]
+    fn test_split_joined_road() {
+        let mut world = World::new();
+        world.insert_resource(RoadIndex::new());
+        world.init_resource::<Events<BuildRoad>>();
+        let mut system = IntoSystem::into_system(build_road_system);
+        system.initialize(&mut world);
+        let mut run = |world: &mut World, events: Vec<BuildRoad>| {
+            world.send_event_batch(events);
+            system.run((), world);
+            system.apply_deferred(world);
+        };
+
+        run(
+            &mut world,
+            vec![build_road(
+                straight(dvec3(-20., 0., 0.), dvec3(0., 0., 0.)),
+                None,
+                None,
+            )],
+        );
+        let first = world.query::<(Entity, &Road)>().single(&world).0;
+        let end = Snap::RoadEnd {
+            road: first,
+            at_end: true,
+        };
+        run(
+            &mut world,
+            vec![build_road(
+                straight(dvec3(0., 0., 0.), dvec3(20., 0., 0.)),
+                Some(end),
+                None,
+            )],
+        );
+        // splitting the second road takes its lanes, and the links into them from the first
+        run(
+            &mut world,
+            vec![build_road(
+                straight(dvec3(10., 0., -20.), dvec3(10., 0., 20.)),
+                None,
+                None,
+            )],
+        );
+
+        assert_eq!(world.query::<&Road>().iter(&world).count(), 5);
+        let mut paths = world.query::<&Path>();
+        let nexts = world
+            .query::<&PathNext>()
+            .iter(&world)
+            .map(|next| next.next)
+            .collect::<Vec<_>>();
+        let prevs = world
+            .query::<&PathPrev>()
+            .iter(&world)
+            .map(|prev| prev.prev)
+            .collect::<Vec<_>>();
+        // the first road's lanes in and out, and in and out of the junction's connectors
+        assert_eq!(nexts.len(), 2 + 2 * 4 * 3);
+        assert_eq!(prevs.len(), nexts.len());
+        for e in nexts.into_iter().chain(prevs) {
+            assert!(paths.get(&world, e).is_ok(), "link to {:?}", e);
+        }
+    }
+
+    #[test]
+    fn test_find_snap() {
+        let mut world = World::new();
+        let mut road_index = RoadIndex::new();
+        let mut spawn_road = |world: &mut World, center: PlacedCurve| {
+            let road = build_road(center, None, None);
+            let road = RoadBlueprint::new(road).to_road();
+            let e = world.spawn(road.clone()).id();
+            road_index.add_road(e, &road);
+            e
+        };
+        let road_e = spawn_road(&mut world, straight(dvec3(0., 0., 0.), dvec3(20., 0., 0.)));
+        // a road ending where the first one starts
+        spawn_road(&mut world, straight(dvec3(-20., 0., 0.), dvec3(0., 0., 0.)));
+        let junction = JunctionBluePrint::new(dvec3(10., 0., 30.), 4.);
+        let junction = Junction {
+            center: junction.center,
+            width: junction.width,
+            footprint: junction.footprint(),
+        };
+        let junction_e = world.spawn(junction.clone()).id();
+        road_index.add_junction(junction_e, &junction);
+
+        let mut state = SystemState::<(Query<&Road>, Query<&Junction>)>::new(&mut world);
+        let (road_query, junction_query) = state.get(&world);
+        let snap = |pt: DVec3| find_snap(pt, &road_index, &road_query, &junction_query);
+
+        assert_eq!(
+            snap(dvec3(20.5, 0., 0.5)),
+            Some((
+                Snap::RoadEnd {
+                    road: road_e,
+                    at_end: true
+                },
+                dvec3(20., 0., 0.)
+            ))
+        );
+        let (on_road, at) = snap(dvec3(10., 0., 1.)).unwrap();
+        assert_eq!(on_road, Snap::OnRoad { road: road_e, at });
+        assert!(at.distance(dvec3(10., 0., 0.)) < 1e-3);
+        // the ends where the roads meet are taken, and there is no room for a junction there
+        assert_eq!(snap(dvec3(0.5, 0., 0.5)), None);
+        assert_eq!(
+            snap(dvec3(10.5, 0., 30.5)),
+            Some((Snap::Junction(junction_e), dvec3(10., 0., 30.)))
+        );
+        assert_eq!(snap(dvec3(10., 0., 10.)), None);
+    }
+
+    #[test]
+    fn test_join_road_ends() {
+        let [forward_1, backward_1, forward_2, backward_2] = [1, 2, 3, 4].map(Entity::from_raw);
+        let (road_1, road_2) = (Entity::from_raw(5), Entity::from_raw(6));
+        let lanes_of = |road_e: Entity| {
+            if road_e == road_1 {
+                (vec![forward_1], vec![backward_1])
+            } else {
+                (vec![forward_2], vec![backward_2])
+            }
+        };
+
+        // from the end of road 1 to the start of road 2
+        let center = straight(dvec3(0., 0., 0.), dvec3(10., 0., 0.));
+        let mut bp = RoadBlueprint::new(build_road(
+            center.clone(),
+            Some(Snap::RoadEnd {
+                road: road_1,
+                at_end: true,
+            }),
+            Some(Snap::RoadEnd {
+                road: road_2,
+                at_end: false,
+            }),
+        ));
+        join_road_ends(&mut bp, lanes_of);
+        assert_eq!(bp.paths[0].0, vec![forward_1]);
+        assert_eq!(bp.paths[0].2, vec![forward_2]);
+        assert_eq!(bp.backward[0].0, vec![backward_2]);
+        assert_eq!(bp.backward[0].2, vec![backward_1]);
+
+        // from the start of road 1, and onto the middle of road 2
+        let at = dvec3(10., 0., 0.);
+        let mut bp = RoadBlueprint::new(build_road(
+            center,
+            Some(Snap::RoadEnd {
+                road: road_1,
+                at_end: false,
+            }),
+            Some(Snap::OnRoad { road: road_2, at }),
+        ));
+        join_road_ends(&mut bp, lanes_of);
+        assert_eq!(bp.paths[0].0, vec![backward_1]);
+        assert_eq!(bp.backward[0].2, vec![forward_1]);
+        assert!(bp.paths[0].2.is_empty() && bp.backward[0].0.is_empty());
+    }
+
+    #[test]
+    fn test_road_index() {
+        let mut index = RoadIndex::new();
+        let add = |index: &mut RoadIndex, i: u32| {
+            let z = 10. * i as f64;
+            let road = build_road(straight(dvec3(0., 0., z), dvec3(50., 0., z)), None, None);
+            index.add_road(Entity::from_raw(i), &RoadBlueprint::new(road).to_road());
+        };
+        let junction_bp = JunctionBluePrint::new(dvec3(60., 0., 0.), 4.);
+        let junction = Junction {
+            center: junction_bp.center,
+            width: junction_bp.width,
+            footprint: junction_bp.footprint(),
+        };
+        let junction_e = Entity::from_raw(100);
+        let area = Aabb::new(vec3(20., -1., 15.), vec3(30., 1., 25.));
+        let check = |index: &RoadIndex| {
+            assert_eq!(
+                index.collisions(&area),
+                vec![CollisionTarget::Road(Entity::from_raw(2))]
+            );
+            assert_eq!(
+                index.within_radius(dvec3(25., 5., 14.), 3.),
+                vec![CollisionTarget::Road(Entity::from_raw(1))]
+            );
+            let (e, projection) = index.nearest_road(dvec3(25., 0., 14.), 5.).unwrap();
+            assert_eq!(e, Entity::from_raw(1));
+            assert!((projection.length - 25.).abs() < 1e-3);
+            assert!(index.nearest_road(dvec3(25., 0., 15.), 4.).is_none());
+            assert_eq!(
+                index.within_radius(dvec3(63., 0., 0.), 1.5),
+                vec![CollisionTarget::Junction(junction_e)]
+            );
+        };
+
+        // new boxes are searched one by one until there are enough of them
+        index.add_junction(junction_e, &junction);
+        for i in 0..10 {
+            add(&mut index, i);
+        }
+        assert_eq!((index.bvh.len(), index.added.len()), (0, 11));
+        check(&index);
+
+        // and then go into the tree together
+        for i in 10..20 {
+            add(&mut index, i);
+        }
+        assert_eq!((index.bvh.len(), index.added.len()), (MAX_STALE + 1, 4));
+        check(&index);
+
+        // removed boxes stay in the tree until the next rebuild, but are not found
+        index.remove(Entity::from_raw(2));
+        assert!(index.collisions(&area).is_empty());
+        assert!(index.removed.contains(&Entity::from_raw(2)));
+        // boxes not in the tree yet are simply dropped
+        index.remove(Entity::from_raw(19));
+        index.remove(Entity::from_raw(19));
+        assert_eq!((index.added.len(), index.removed.len()), (3, 1));
+        assert_eq!(index.roads.len(), 18);
+
+        // once enough has changed the tree is rebuilt without them, and the boxes added
+        // since go in with it
+        for i in 3..18 {
+            index.remove(Entity::from_raw(i));
+        }
+        assert_eq!((index.bvh.len(), index.added.len()), (6, 0));
+        assert_eq!(index.removed.len(), 2);
+        assert_eq!(
+            index.within_radius(dvec3(25., 0., 0.), 1000.).len(),
+            index.roads.len() + index.junctions.len()
+        );
+        assert_eq!(
+            index.nearest_road(dvec3(25., 0., 14.), 5.).unwrap().0,
+            Entity::from_raw(1)
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let span = |kind, start, end| Span { kind, start, end };
+        let spans = merge_spans(vec![
+            span(SpanKind::Tunnel, 20., 30.),
+            span(SpanKind::Bridge, 8., 15.),
+            span(SpanKind::Bridge, 5., 10.),
+            span(SpanKind::Tunnel, 15., 20.),
+        ]);
+        // touching spans of different kinds stay apart
+        assert_eq!(
+            spans,
+            vec![
+                span(SpanKind::Bridge, 5., 15.),
+                span(SpanKind::Tunnel, 15., 30.),
+            ]
+        );
+        assert_eq!(
+            slice_spans(&spans, 10., 25.),
+            vec![
+                span(SpanKind::Bridge, 0., 5.),
+                span(SpanKind::Tunnel, 5., 15.),
+            ]
+        );
+        assert!(slice_spans(&spans, 30., 40.).is_empty());
+
+        let road = |p: DVec3, q: DVec3| {
+            RoadBlueprint::new(build_road(straight(p, q), None, None)).to_road()
+        };
+        let mut upper = road(dvec3(0., 5., 0.), dvec3(40., 5., 0.));
+        let reach = upper.width();
+        // under it, within the span already there, too close to pass and above it
+        let lower = road(dvec3(20., 0., -20.), dvec3(20., 0., 20.));
+        for other in [
+            &lower,
+            &road(dvec3(22., 0., -20.), dvec3(22., 0., 20.)),
+            &road(dvec3(10., 4., -20.), dvec3(10., 4., 20.)),
+            &road(dvec3(30., 10., -20.), dvec3(30., 10., 20.)),
+        ] {
+            upper.add_spans_over(other);
+        }
+        assert_eq!(upper.spans.len(), 2);
+        let close = |a: &Span, kind, start: f32, end: f32| {
+            a.kind == kind && (a.start - start).abs() < 1e-3 && (a.end - end).abs() < 1e-3
+        };
+        assert!(close(
+            &upper.spans[0],
+            SpanKind::Bridge,
+            20. - reach,
+            20. + reach
+        ));
+        assert!(close(
+            &upper.spans[1],
+            SpanKind::Tunnel,
+            30. - reach,
+            30. + reach
+        ));
+
+        // the road below goes through a tunnel, ending where the road does
+        let mut lower = road(dvec3(20., 0., -20.), dvec3(20., 0., 1.));
+        lower.add_spans_over(&upper);
+        assert_eq!(lower.spans.len(), 1);
+        assert!(close(&lower.spans[0], SpanKind::Tunnel, 20. - reach, 21.));
+    }
+
+    #[test]
+    fn test_lanes() {
+        let two_way = Lanes::two_way(2, 3.5, 0.5);
+        assert_eq!(two_way.width(), 15.);
+        assert_eq!(two_way.offsets(), vec![1.75, 5.25]);
+        let one_way = Lanes::one_way(3, 3., 0.5);
+        assert_eq!(one_way.width(), 10.);
+        assert_eq!(one_way.offsets(), vec![-3., 0., 3.]);
+
+        // forward lanes keep to the right of the center, backward lanes to the right of the
+        // reversed center, each from the leftmost lane
+        let center = straight(dvec3(0., 0., 0.), dvec3(20., 0., 0.));
+        let mut event = build_road(center.clone(), None, None);
+        event.lanes = two_way;
+        let bp = RoadBlueprint::new(event.clone());
+        let starts = |lanes: &Vec<Lane>| {
+            lanes
+                .iter()
+                .map(|(_, path, _)| path.curve.start())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            starts(&bp.paths),
+            vec![dvec3(0., 0., 1.75), dvec3(0., 0., 5.25)]
+        );
+        assert_eq!(
+            starts(&bp.backward),
+            vec![dvec3(20., 0., -1.75), dvec3(20., 0., -5.25)]
+        );
+        event.lanes = one_way;
+        let bp = RoadBlueprint::new(event);
+        assert_eq!(
+            starts(&bp.paths),
+            vec![dvec3(0., 0., -3.), dvec3(0., 0., 0.), dvec3(0., 0., 3.)]
+        );
+        assert!(bp.backward.is_empty());
+        assert_eq!(bp.to_road().speed_max, bp.event.speed_max);
+    }
+
+    #[test]
+    fn test_backward_lanes() {
+        let [from, next, backward_from, backward_next] = [1, 2, 3, 4].map(Entity::from_raw);
+        let mut bp = RoadBlueprint::new(build_road(
+            straight(dvec3(0., 0., 0.), dvec3(20., 0., 0.)),
+            None,
+            None,
+        ));
+        bp.paths[0].0 = vec![from];
+        bp.paths[0].2 = vec![next];
+        bp.backward[0].0 = vec![backward_from];
+        bp.backward[0].2 = vec![backward_next];
+        let width = bp.event.width() as f64;
+
+        // backward lanes run from road b to road a, and keep their links at the far ends
+        let (road_a, _, road_b) = split_road(bp, dvec3(10., 0., 0.)).unwrap();
+        let links = |lane: &Lane| (lane.0.clone(), lane.2.clone());
+        assert_eq!(links(&road_a.paths[0]), (vec![from], vec![]));
+        assert_eq!(links(&road_b.paths[0]), (vec![], vec![next]));
+        assert_eq!(links(&road_b.backward[0]), (vec![backward_from], vec![]));
+        assert_eq!(links(&road_a.backward[0]), (vec![], vec![backward_next]));
+        let (a_back, b_back) = (&road_a.backward[0].1.curve, &road_b.backward[0].1.curve);
+        assert!(b_back.start().distance(dvec3(20., 0., -0.75)) < 1e-3);
+        assert!(b_back.end().distance(dvec3(10. + width / 2., 0., -0.75)) < 1e-3);
+        assert!(a_back.start().distance(dvec3(10. - width / 2., 0., -0.75)) < 1e-3);
+        assert!(a_back.end().distance(dvec3(0., 0., -0.75)) < 1e-3);
+
+        // through the junction between them, forward lanes go on forward and backward lanes
+        // backward, without turning back onto their own road
+        let paths = |lanes: &Vec<Lane>| lanes.iter().map(|l| l.1.clone()).collect::<Vec<_>>();
+        let connections = spawn_junction_full_connections(
+            vec![paths(&road_a.paths), paths(&road_b.backward)],
+            vec![paths(&road_a.backward), paths(&road_b.paths)],
+        )
+        .unwrap();
+        assert_eq!(connections.len(), 2);
+        let forward = &connections[&(0, 0, 1, 0)].curve;
+        assert!(forward.start().distance(road_a.paths[0].1.curve.end()) < 1e-3);
+        assert!(forward.end().distance(road_b.paths[0].1.curve.start()) < 1e-3);
+        let backward = &connections[&(1, 0, 0, 0)].curve;
+        assert!(backward.start().distance(b_back.end()) < 1e-3);
+        assert!(backward.end().distance(a_back.start()) < 1e-3);
+
+        // lanes are told apart by where they run, and a lane split again keeps every link
+        let road = blueprint_of(
+            &road_a.to_road(),
+            vec![
+                (
+                    Entity::from_raw(5),
+                    (
+                        vec![],
+                        road_a.backward[0].1.clone(),
+                        vec![next, backward_next],
+                    ),
+                ),
+                (Entity::from_raw(6), road_a.paths[0].clone()),
+            ],
+        );
+        assert_eq!(links(&road.paths[0]), (vec![from], vec![]));
+        assert_eq!(
+            links(&road.backward[0]),
+            (vec![], vec![next, backward_next])
+        );
+        let (first, _, _) = split_road(road, dvec3(2.5, 0., 0.)).unwrap();
+        assert_eq!(
+            links(&first.backward[0]),
+            (vec![], vec![next, backward_next])
+        );
     }
 }
@@ -0,0 +1,318 @@
+//! the meshes roads are drawn with: an asphalt strip along the center curve, a deck slab
+//! under the stretches that are bridges, and walls and a roof over those that are tunnels.
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+};
+use cage::core::math::curve::Curve;
+
+use super::road::{Road, SpanKind, CLEARANCE};
+use crate::plugins::camera::RenderOrigin;
+
+/// how far the mesh may stray from the road curve, in meters
+pub const MESH_TOLERANCE: f32 = 0.01;
+/// the surface floats this much above the curve, so flat roads don't flicker with the ground
+const SURFACE_LIFT: f32 = 0.01;
+/// thickness of a bridge deck
+const DECK_DEPTH: f32 = 0.5;
+
+#[derive(Resource)]
+pub struct RoadMaterial(pub Handle<StandardMaterial>);
+
+/// a cut across the road at some arc length
+struct Section {
+    length: f32,
+    left: Vec3,
+    right: Vec3,
+    /// up from the surface, tilted by the grade
+    normal: Vec3,
+}
+
+/// sections where the flattened |center| has its vertices, from |start| on along the road
+fn sections(center: &Curve, width: f32, start: f32) -> Vec<Section> {
+    center
+        .flatten(MESH_TOLERANCE)
+        .into_iter()
+        .map(|(length, p)| {
+            let tangent = center.frenet_at_length(length).tangent;
+            let right = Vec3::new(-tangent.z, 0., tangent.x).normalize_or_zero();
+            let normal = right.cross(tangent).normalize_or_zero();
+            let lift = Vec3::Y * SURFACE_LIFT;
+            Section {
+                length: start + length,
+                left: p - right * width / 2. + lift,
+                right: p + right * width / 2. + lift,
+                normal,
+            }
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct MeshBuilder {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<Vec2>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    /// a band of quads between |a| and |b| at each section, facing along |normal|. u goes
+    /// from 0 at |a| to 1 at |b|, v is the arc length in road widths.
+    fn strip(
+        &mut self,
+        sections: &[Section],
+        width: f32,
+        a: impl Fn(&Section) -> Vec3,
+        b: impl Fn(&Section) -> Vec3,
+        normal: impl Fn(&Section) -> Vec3,
+    ) {
+        let base = self.positions.len() as u32;
+        for section in sections {
+            let v = section.length / width;
+            self.positions.extend([a(section), b(section)]);
+            self.normals.extend([normal(section); 2]);
+            self.uvs.extend([Vec2::new(0., v), Vec2::new(1., v)]);
+        }
+        for i in 0..sections.len().saturating_sub(1) as u32 {
+            let (a0, b0, a1, b1) = (
+                base + 2 * i,
+                base + 2 * i + 1,
+                base + 2 * i + 2,
+                base + 2 * i + 3,
+            );
+            self.indices.extend([a0, b0, a1, a1, b0, b1]);
+        }
+    }
+
+    fn build(self) -> Mesh {
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs)
+        .with_inserted_indices(Indices::U32(self.indices))
+    }
+}
+
+/// the surface of |road| around the origin of its center, plus the sides and underside of
+/// its bridges and the walls and roof of its tunnels
+pub fn road_mesh(road: &Road) -> Mesh {
+    let mut builder = MeshBuilder::default();
+    let width = road.width();
+    let center = road.center.local();
+    let surface = sections(center, width, 0.);
+    builder.strip(&surface, width, |s| s.left, |s| s.right, |s| s.normal);
+
+    for span in road.spans.iter().filter(|s| s.kind == SpanKind::Bridge) {
+        let Ok(deck) = center.slice_by_length(span.start, span.end) else {
+            continue;
+        };
+        let deck = sections(&deck, width, span.start);
+        let down = Vec3::Y * DECK_DEPTH;
+        // outwards on both sides: left is -right, right is +right
+        let side = |s: &Section| (s.right - s.left).normalize_or_zero();
+        builder.strip(&deck, width, |s| s.left - down, |s| s.left, |s| -side(s));
+        builder.strip(&deck, width, |s| s.right, |s| s.right - down, side);
+        builder.strip(
+            &deck,
+            width,
+            |s| s.right - down,
+            |s| s.left - down,
+            |s| -s.normal,
+        );
+    }
+
+    for span in road.spans.iter().filter(|s| s.kind == SpanKind::Tunnel) {
+        let Ok(bore) = center.slice_by_length(span.start, span.end) else {
+            continue;
+        };
+        let bore = sections(&bore, width, span.start);
+        let up = Vec3::Y * CLEARANCE;
+        // the walls and ceiling face the road, the roof faces the sky
+        let side = |s: &Section| (s.right - s.left).normalize_or_zero();
+        builder.strip(&bore, width, |s| s.left + up, |s| s.left, side);
+        builder.strip(&bore, width, |s| s.right, |s| s.right + up, |s| -side(s));
+        builder.strip(
+            &bore,
+            width,
+            |s| s.right + up,
+            |s| s.left + up,
+            |s| -s.normal,
+        );
+        builder.strip(
+            &bore,
+            width,
+            |s| s.left + up,
+            |s| s.right + up,
+            |s| s.normal,
+        );
+    }
+    builder.build()
+}
+
+pub fn setup_road_material(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let material = materials.add(StandardMaterial {
+        base_color: Color::rgb(0.2, 0.2, 0.22),
+        perceptual_roughness: 0.9,
+        ..default()
+    });
+    commands.insert_resource(RoadMaterial(material));
+}
+
+/// (re)build the mesh of every new or changed road. the mesh lives on the road entity, so it
+/// goes away with it when a road is split or removed, and is placed where the origin of the
+/// road's center is rendered.
+pub fn update_road_meshes(
+    mut commands: Commands,
+    roads: Query<(Entity, &Road), Changed<Road>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    material: Res<RoadMaterial>,
+    origin: Res<RenderOrigin>,
+) {
+    for (road_e, road) in roads.iter() {
+        commands.entity(road_e).insert(PbrBundle {
+            mesh: meshes.add(road_mesh(road)),
+            material: material.0.clone(),
+            transform: Transform::from_translation(origin.to_render(road.center.origin())),
+            ..default()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{vec3, DVec3};
+    use cage::core::math::curve::{line::LineSegment, placed::PlacedCurve};
+
+    use super::*;
+    use crate::plugins::transport::road::{Lanes, Span};
+
+    fn road(spans: Vec<Span>) -> Road {
+        Road {
+            center: PlacedCurve::new(
+                DVec3::ZERO,
+                LineSegment::try_new([vec3(0., 0., 0.), vec3(20., 0., 0.)])
+                    .unwrap()
+                    .to_curve(),
+            ),
+            lanes: Lanes::two_way(1, 1.5, 0.25),
+            speed_max: 10.,
+            travel_time_avg: 1.,
+            spans,
+        }
+    }
+
+    fn positions(mesh: &Mesh) -> Vec<Vec3> {
+        let positions = mesh.attribute(Mesh::ATTRIBUTE_POSITION).unwrap();
+        positions
+            .as_float3()
+            .unwrap()
+            .iter()
+            .map(|p| Vec3::from(*p))
+            .collect()
+    }
+
+    fn normals(mesh: &Mesh) -> Vec<Vec3> {
+        let normals = mesh.attribute(Mesh::ATTRIBUTE_NORMAL).unwrap();
+        normals
+            .as_float3()
+            .unwrap()
+            .iter()
+            .map(|p| Vec3::from(*p))
+            .collect()
+    }
+
+    /// the normal of every triangle from its winding
+    fn faces(mesh: &Mesh) -> Vec<Vec3> {
+        let positions = positions(mesh);
+        let indices = mesh.indices().unwrap().iter().collect::<Vec<_>>();
+        indices
+            .chunks(3)
+            .map(|t| {
+                let [a, b, c] = [t[0], t[1], t[2]].map(|i| positions[i]);
+                (b - a).cross(c - a).normalize()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_strip() {
+        let sections = (0..3)
+            .map(|i| Section {
+                length: i as f32,
+                left: vec3(i as f32, 0., -1.),
+                right: vec3(i as f32, 0., 1.),
+                normal: Vec3::Y,
+            })
+            .collect::<Vec<_>>();
+        let mut builder = MeshBuilder::default();
+        builder.strip(&sections, 2., |s| s.left, |s| s.right, |s| s.normal);
+        assert_eq!(builder.positions.len(), 6);
+        assert_eq!(builder.indices.len(), 2 * 6);
+        assert_eq!(builder.uvs[5], Vec2::new(1., 1.));
+        // a second strip starts at its own vertices
+        builder.strip(&sections[..1], 2., |s| s.left, |s| s.right, |s| s.normal);
+        assert_eq!(builder.positions.len(), 8);
+        assert_eq!(builder.indices.len(), 2 * 6);
+
+        let mesh = builder.build();
+        assert!(normals(&mesh).iter().all(|n| *n == Vec3::Y));
+        assert!(faces(&mesh).iter().all(|n| n.distance(Vec3::Y) < 1e-6));
+    }
+
+    #[test]
+    fn test_road_mesh() {
+        // a straight road needs a section at each end only
+        let plain = road(vec![]);
+        let mesh = road_mesh(&plain);
+        let surface = positions(&mesh);
+        assert_eq!((surface.len(), mesh.indices().unwrap().len()), (4, 6));
+        assert!((surface[0].distance(surface[1]) - plain.width()).abs() < 1e-5);
+        assert!(surface.iter().all(|p| p.y == SURFACE_LIFT));
+        assert!(faces(&mesh).iter().all(|n| n.distance(Vec3::Y) < 1e-6));
+
+        // a deck hangs off both sides and runs under the bridge
+        let span = |kind| Span {
+            kind,
+            start: 5.,
+            end: 15.,
+        };
+        let mesh = road_mesh(&road(vec![span(SpanKind::Bridge)]));
+        let deck = positions(&mesh)[4..].to_vec();
+        assert_eq!((deck.len(), mesh.indices().unwrap().len()), (3 * 4, 4 * 6));
+        assert!(deck.iter().all(|p| (5. ..=15.).contains(&p.x)));
+        assert!((deck[0].distance(deck[1]) - DECK_DEPTH).abs() < 1e-5);
+        assert!((deck[8].distance(deck[9]) - plain.width()).abs() < 1e-5);
+        // every face matches its vertex normals and faces out of the deck
+        let (winding, vertex_normals) = (faces(&mesh), normals(&mesh));
+        for (i, face) in winding.iter().enumerate().skip(2) {
+            assert!(face.distance(vertex_normals[4 + (i - 2) / 2 * 4]) < 1e-6);
+        }
+        assert_eq!(winding[2], Vec3::NEG_Z);
+        assert_eq!(winding[4], Vec3::Z);
+        assert_eq!(winding[6], Vec3::NEG_Y);
+
+        // a tunnel has walls as high as the clearance, and a ceiling above the road
+        let mesh = road_mesh(&road(vec![span(SpanKind::Tunnel)]));
+        let bore = positions(&mesh)[4..].to_vec();
+        assert_eq!((bore.len(), mesh.indices().unwrap().len()), (4 * 4, 5 * 6));
+        assert!((bore[0].distance(bore[1]) - CLEARANCE).abs() < 1e-5);
+        assert!((bore[8].distance(bore[9]) - plain.width()).abs() < 1e-5);
+        let (winding, vertex_normals) = (faces(&mesh), normals(&mesh));
+        for (i, face) in winding.iter().enumerate().skip(2) {
+            assert!(face.distance(vertex_normals[4 + (i - 2) / 2 * 4]) < 1e-6);
+        }
+        // the walls face in, the ceiling down and the roof up
+        assert_eq!(winding[2], Vec3::Z);
+        assert_eq!(winding[4], Vec3::NEG_Z);
+        assert_eq!(winding[6], Vec3::NEG_Y);
+        assert_eq!(winding[8], Vec3::Y);
+    }
+}